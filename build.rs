@@ -0,0 +1,33 @@
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Embeds the git commit and build time so that a running monitor can report
+/// which build it is. `GIT_COMMIT` takes precedence, for builds without `.git`.
+fn main() {
+    let git_commit = env::var("GIT_COMMIT").ok()
+        .filter(|commit| !commit.is_empty())
+        .or_else(|| {
+            Command::new("git").args(["rev-parse", "--short=12", "HEAD"]).output().ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+                .map(|commit| commit.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let build_timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    println!("cargo:rustc-env=STATUSSENTINEL_GIT_COMMIT={}", git_commit);
+    println!("cargo:rustc-env=STATUSSENTINEL_BUILD_TIMESTAMP={}", build_timestamp);
+
+    // Source changes keep the timestamp current, git changes the commit.
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
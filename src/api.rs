@@ -0,0 +1,637 @@
+use std::{
+    time::Duration,
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicI64, Ordering},
+    },
+};
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde_json::{json, Map, Value};
+use tokio::net::TcpListener;
+
+use crate::cache::{RECENT_RESULTS, RecentResult, ServiceCache};
+use crate::config::Config;
+use crate::database::{CheckTiming, DbPool, DnsRecord, EXTERNAL_INCIDENT_SOURCE, Incident, IncidentNote, MonitoringError, Service, Severity};
+use crate::metrics::Metrics;
+use crate::monitor::{ServiceStates, check_service};
+use crate::notify::{Observers, notify_close, notify_open};
+use crate::request::HttpClients;
+use crate::sla::{self, SlaReport};
+use crate::statuspage::{self, ServiceStatus};
+use crate::telemetry::instrument_check;
+use crate::version::BuildInfo;
+
+
+/// Timestamp of the last monitoring cycle that ran to completion, shared
+/// between the monitoring loop and the health endpoint.
+#[derive(Clone, Default)]
+pub struct LastCycle(Arc<AtomicI64>);
+
+impl LastCycle {
+    pub fn mark(&self) {
+        self.0.store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    pub fn elapsed(&self) -> Option<Duration> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            timestamp => Some(Duration::from_secs((Utc::now().timestamp() - timestamp).max(0) as u64)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiState {
+    pub db_pool: DbPool,
+    pub last_cycle: LastCycle,
+    pub check_interval: Duration,
+    pub metrics: Metrics,
+    pub cache: ServiceCache,
+    pub observers: Arc<Observers>,
+    pub service_states: ServiceStates,
+    pub http_clients: HttpClients,
+    pub config: Arc<Config>,
+    pub display_timezone: Tz,
+}
+
+pub async fn serve(address: SocketAddr, state: ApiState) -> Result<(), MonitoringError> {
+    let listener = TcpListener::bind(address).await?;
+    axum::serve(listener, router(state)).await?;
+
+    Ok(())
+}
+
+fn router(state: ApiState) -> Router {
+    let mutating = Router::new()
+        .route("/services/{id}/check", post(check_now))
+        .route("/incidents", post(open_incident))
+        .route("/incidents/{id}/resolve", post(resolve_incident))
+        .route("/incidents/{id}/notes", post(add_incident_note))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
+
+    let mut reads = Router::new()
+        .route("/", get(status_page))
+        .route("/version", get(version))
+        .route("/services", get(list_services))
+        .route("/services/{id}/timings", get(check_timings))
+        .route("/services/{id}/slo", get(slo_compliance))
+        .route("/services/{id}/recent", get(recent_results))
+        .route("/services/{id}/timeseries", get(timeseries))
+        .route("/services/{id}/badge.svg", get(badge))
+        .route("/services/{id}/dns", get(dns_records))
+        .route("/metrics", get(metrics))
+        .route("/incidents/recent", get(recent_incidents))
+        .route("/sla", get(sla));
+    if !state.config.public_reads {
+        reads = reads.route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
+    }
+
+    // Health checks come from probes that don't carry the token.
+    Router::new()
+        .route("/healthz", get(healthz))
+        .merge(reads)
+        .merge(mutating)
+        .with_state(state)
+}
+
+async fn healthz(State(state): State<ApiState>) -> (StatusCode, Json<Value>) {
+    // A cycle is followed by a full interval of sleep, so allow one extra
+    // interval for the checks themselves before reporting the loop as stalled.
+    let last_cycle = state.last_cycle.elapsed();
+    let loop_healthy = last_cycle.is_some_and(|elapsed| elapsed <= state.check_interval * 2);
+    let database_healthy = state.db_pool.check_connection().await.is_ok();
+
+    let status = if loop_healthy && database_healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(json!({
+        "status": if status == StatusCode::OK { "ok" } else { "unavailable" },
+        "database": database_healthy,
+        "last_cycle_secs_ago": last_cycle.map(|elapsed| elapsed.as_secs()),
+    })))
+}
+
+async fn version() -> Json<BuildInfo> {
+    Json(BuildInfo::current())
+}
+
+impl IntoResponse for MonitoringError {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": self.0 }))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct ServicesQuery {
+    tag: Option<String>,
+}
+
+async fn list_services(
+    State(state): State<ApiState>,
+    Query(query): Query<ServicesQuery>,
+) -> Result<Json<Vec<Service>>, MonitoringError> {
+    Ok(Json(current_services(&state, query.tag.as_deref()).await?))
+}
+
+/// Services with their latest results, from the cache once every service has
+/// been checked and from the database before that.
+pub async fn current_services(state: &ApiState, tag: Option<&str>) -> Result<Vec<Service>, MonitoringError> {
+    if let Some(cached) = state.cache.services() {
+        let services = cached.iter()
+            .filter(|cached| tag.is_none_or(|tag| cached.service.tags.iter().any(|t| t == tag)))
+            .map(|cached| cached.service.clone())
+            .collect();
+        return Ok(services);
+    }
+
+    let mut services = match tag {
+        Some(tag) => state.db_pool.list_services_by_tag(tag).await?,
+        None => state.db_pool.list_services().await?,
+    };
+
+    for service in &mut services {
+        service.stats = Some(state.db_pool.service_stats(&service.id).await?);
+    }
+
+    Ok(services)
+}
+
+#[derive(Deserialize)]
+struct CheckTimingsQuery {
+    limit: Option<i64>,
+}
+
+async fn check_timings(
+    State(state): State<ApiState>,
+    Path(service_id): Path<String>,
+    Query(query): Query<CheckTimingsQuery>,
+) -> Result<Json<Vec<CheckTiming>>, MonitoringError> {
+    let limit = query.limit.unwrap_or(60).clamp(1, 1000);
+    let timings = state.db_pool.list_check_timings(&service_id, limit).await?;
+
+    Ok(Json(timings))
+}
+
+#[derive(Deserialize)]
+struct DnsRecordsQuery {
+    limit: Option<i64>,
+}
+
+async fn dns_records(
+    State(state): State<ApiState>,
+    Path(service_id): Path<String>,
+    Query(query): Query<DnsRecordsQuery>,
+) -> Result<Json<Vec<DnsRecord>>, MonitoringError> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 1000);
+    Ok(Json(state.db_pool.list_dns_records(&service_id, limit).await?))
+}
+
+#[derive(Deserialize)]
+struct RecentResultsQuery {
+    n: Option<usize>,
+}
+
+async fn recent_results(
+    State(state): State<ApiState>,
+    Path(service_id): Path<String>,
+    Query(query): Query<RecentResultsQuery>,
+) -> Json<Vec<RecentResult>> {
+    let limit = query.n.unwrap_or(50).clamp(1, RECENT_RESULTS);
+    Json(state.cache.recent(&service_id, limit))
+}
+
+#[derive(Deserialize)]
+struct SloQuery {
+    window: Option<i32>,
+}
+
+async fn slo_compliance(
+    State(state): State<ApiState>,
+    Path(service_id): Path<String>,
+    Query(query): Query<SloQuery>,
+) -> Result<Json<Value>, MonitoringError> {
+    // One sample per minute, so the default window is a day.
+    let window = query.window.unwrap_or(1440).max(1);
+    let compliance_percentage = state.db_pool.slo_compliance(&service_id, window).await?;
+
+    Ok(Json(json!({
+        "service_id": service_id,
+        "window": window,
+        "compliance_percentage": compliance_percentage,
+    })))
+}
+
+/// Most buckets one timeseries request may ask for.
+const MAX_TIMESERIES_POINTS: i64 = 10_000;
+
+#[derive(Deserialize)]
+struct TimeseriesQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// Bucket size in seconds.
+    resolution: Option<i64>,
+}
+
+async fn timeseries(
+    State(state): State<ApiState>,
+    Path(service_id): Path<String>,
+    Query(query): Query<TimeseriesQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(1));
+    let range_secs = (to - from).num_seconds();
+    if range_secs <= 0 {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+
+    // By default about 500 buckets, never finer than the check interval.
+    let resolution = query.resolution
+        .unwrap_or_else(|| (range_secs / 500).max(state.check_interval.as_secs() as i64));
+    if resolution < 1 {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "resolution must be at least 1 second".to_string()));
+    }
+    if range_secs / resolution > MAX_TIMESERIES_POINTS {
+        return Err(ApiError(StatusCode::BAD_REQUEST, format!(
+            "The range covers more than {} buckets, use a coarser resolution", MAX_TIMESERIES_POINTS
+        )));
+    }
+
+    if state.db_pool.get_service(&service_id).await?.is_none() {
+        return Err(ApiError(StatusCode::NOT_FOUND, format!("No service with id {}", service_id)));
+    }
+    let points = state.db_pool.timeseries(&service_id, from, to, resolution).await?;
+
+    Ok(Json(json!({
+        "service_id": service_id,
+        "from": from,
+        "to": to,
+        "resolution": resolution,
+        "points": points,
+    })))
+}
+
+#[derive(Deserialize)]
+struct BadgeQuery {
+    label: Option<String>,
+    #[serde(default)]
+    uptime: bool,
+}
+
+/// Badge with the service's current status for embedding in READMEs. Served
+/// from the cache, or from the database until the service's first check.
+async fn badge(
+    State(state): State<ApiState>,
+    Path(service_id): Path<String>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response, ApiError> {
+    let (service, uptime_percentage) = match state.cache.service(&service_id) {
+        Some(cached) => (cached.service.clone(), cached.uptime_percentage),
+        None => {
+            let service = state.db_pool.get_service(&service_id).await?
+                .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No service with id {}", service_id)))?;
+            let uptime_percentage = state.db_pool.uptime_percentage(&service_id).await?;
+            (service, uptime_percentage)
+        }
+    };
+
+    let (status, color) = match (service.response_times.is_empty(), service.is_online) {
+        (true, _) => ("unknown", "#6e7781"),
+        (false, true) => ("up", "#1a7f37"),
+        (false, false) => ("down", "#cf222e"),
+    };
+    let message = match (query.uptime, uptime_percentage) {
+        (true, Some(uptime)) => format!("{} | {:.2}%", status, uptime),
+        _ => status.to_string(),
+    };
+    let svg = statuspage::render_badge(query.label.as_deref().unwrap_or(&service.name), &message, color);
+
+    // Image proxies such as GitHub's camo honor this, so the badge follows each check.
+    let cache_control = format!("max-age={}", state.check_interval.as_secs());
+    Ok((
+        [(header::CONTENT_TYPE, "image/svg+xml".to_string()), (header::CACHE_CONTROL, cache_control)],
+        svg,
+    ).into_response())
+}
+
+async fn metrics(State(state): State<ApiState>) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    ).into_response()
+}
+
+#[derive(Deserialize)]
+struct RecentIncidentsQuery {
+    limit: Option<i64>,
+    service: Option<String>,
+}
+
+async fn recent_incidents(
+    State(state): State<ApiState>,
+    Query(query): Query<RecentIncidentsQuery>,
+) -> Result<Json<Vec<Incident>>, MonitoringError> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 1000);
+    let incidents = state.db_pool.list_recent_incidents(limit, query.service.as_deref()).await?;
+
+    Ok(Json(incidents))
+}
+
+async fn status_page(State(state): State<ApiState>) -> Result<Html<String>, MonitoringError> {
+    let mut statuses = Vec::new();
+    let mut any_open_incident = true;
+
+    match state.cache.services() {
+        Some(cached) => {
+            any_open_incident = cached.iter().any(|cached| cached.has_open_incident);
+            for cached in cached {
+                statuses.push(ServiceStatus {
+                    service: cached.service.clone(),
+                    uptime_percentage: cached.uptime_percentage,
+                });
+            }
+        }
+        None => {
+            for service in state.db_pool.list_services().await? {
+                let uptime_percentage = state.db_pool.uptime_percentage(&service.id).await?;
+                statuses.push(ServiceStatus { service, uptime_percentage });
+            }
+        }
+    }
+    statuses.sort_by(|a, b| a.service.name.cmp(&b.service.name));
+
+    let open_incidents = if any_open_incident {
+        state.db_pool.list_incidents(false).await?
+    } else {
+        Vec::new()
+    };
+
+    Ok(Html(statuspage::render(&statuses, &open_incidents, state.display_timezone)))
+}
+
+/// Incident reported by an external system, such as a deploy pipeline.
+#[derive(Deserialize)]
+struct NewIncident {
+    service_id: String,
+    description: String,
+    #[serde(default)]
+    severity: Severity,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+}
+
+/// Update posted to an incident's timeline.
+#[derive(Deserialize)]
+struct NewIncidentNote {
+    #[serde(default = "default_note_author")]
+    author: String,
+    note: String,
+}
+
+fn default_note_author() -> String {
+    "api".to_string()
+}
+
+/// Error answered with a status other than 500.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<MonitoringError> for ApiError {
+    fn from(err: MonitoringError) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.0)
+    }
+}
+
+/// Lets a request through with `Authorization: Bearer <ADMIN_TOKEN>`, comparing
+/// in constant time. Routes behind it answer 404 while no token is configured.
+async fn require_admin(
+    State(state): State<ApiState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(expected) = &state.config.admin_token else {
+        return Err(ApiError(StatusCode::NOT_FOUND, "This endpoint is disabled, set ADMIN_TOKEN to enable it".to_string()));
+    };
+
+    let token = request.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let matches = token.len() == expected.len()
+        && token.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+
+    if matches {
+        Ok(next.run(request).await)
+    } else {
+        Err(ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token".to_string()))
+    }
+}
+
+async fn open_incident(
+    State(state): State<ApiState>,
+    Json(new_incident): Json<NewIncident>,
+) -> Result<(StatusCode, Json<Incident>), ApiError> {
+    let service = state.db_pool.get_service(&new_incident.service_id).await?
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No service with id {}", new_incident.service_id)))?;
+
+    // Marked so that the monitor leaves it open while its own checks pass.
+    let mut metadata = new_incident.metadata;
+    metadata.insert("source".to_string(), json!(EXTERNAL_INCIDENT_SOURCE));
+
+    let incident = state.db_pool
+        .add_incident(&service.id, &new_incident.description, new_incident.severity, Value::Object(metadata))
+        .await?;
+    notify_open(&state.observers, &service.channels, &incident).await;
+
+    Ok((StatusCode::CREATED, Json(incident)))
+}
+
+async fn resolve_incident(
+    State(state): State<ApiState>,
+    Path(incident_id): Path<i32>,
+) -> Result<Json<Incident>, ApiError> {
+    let incident = state.db_pool.end_incident(incident_id).await?
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No open incident with id {}", incident_id)))?;
+
+    let channels = state.db_pool.get_service(&incident.service_id).await?
+        .map(|service| service.channels)
+        .unwrap_or_default();
+    notify_close(&state.observers, &channels, &incident).await;
+
+    Ok(Json(incident))
+}
+
+/// Adds a note to an open or resolved incident, such as a post-mortem link.
+async fn add_incident_note(
+    State(state): State<ApiState>,
+    Path(incident_id): Path<i32>,
+    Json(new_note): Json<NewIncidentNote>,
+) -> Result<(StatusCode, Json<IncidentNote>), ApiError> {
+    if new_note.author.trim().is_empty() || new_note.note.trim().is_empty() {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "author and note must not be empty".to_string()));
+    }
+
+    let note = state.db_pool.add_incident_note(incident_id, new_note.author.trim(), new_note.note.trim()).await?
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No incident with id {}", incident_id)))?;
+
+    Ok((StatusCode::CREATED, Json(note)))
+}
+
+/// Runs the service's checks right away and records the sample, opening or
+/// resolving incidents just as a monitoring cycle would.
+async fn check_now(
+    State(state): State<ApiState>,
+    Path(service_id): Path<String>,
+) -> Result<Json<RecentResult>, ApiError> {
+    let service = state.db_pool.get_service(&service_id).await?
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No service with id {}", service_id)))?;
+    let result = instrument_check(&service, check_service(
+        &service, &state.db_pool, &state.service_states, &state.metrics, &state.cache, &state.observers,
+        &state.http_clients, &state.config, &AtomicBool::new(false),
+    )).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct SlaQuery {
+    target: Option<f64>,
+    days: Option<i32>,
+}
+
+async fn sla(
+    State(state): State<ApiState>,
+    Query(query): Query<SlaQuery>,
+) -> Result<Json<Vec<SlaReport>>, MonitoringError> {
+    let reports = sla::sla_reports(
+        &state.db_pool,
+        query.target.unwrap_or(sla::DEFAULT_TARGET_PERCENTAGE),
+        query.days.unwrap_or(sla::DEFAULT_WINDOW_DAYS),
+    ).await?;
+
+    Ok(Json(reports))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    /// Without a database: the pool only connects once a handler queries it.
+    async fn test_router(admin_token: Option<&str>, public_reads: bool) -> Router {
+        let config = Config {
+            database_host: "127.0.0.1".to_string(),
+            database_port: 1,
+            database_name: "status_data".to_string(),
+            database_user: "postgres".to_string(),
+            database_password: String::new(),
+            api_address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            display_timezone: Tz::UTC,
+            admin_token: admin_token.map(str::to_string),
+            public_reads,
+            ipc_socket: None,
+            otlp_endpoint: None,
+            user_agent: String::new(),
+            client_cert_path: None,
+            client_key_path: None,
+            check_proxy: None,
+            bind_address: None,
+            alert_cooldown: None,
+            cycle_deadline: None,
+            breaker_threshold: None,
+            breaker_max_backoff: 16,
+            coordinate_incidents: false,
+            dns_record_interval: None,
+            max_samples: 100,
+            online_samples: 3,
+            response_time_unit: Default::default(),
+            incident_template: None,
+            degraded_incident_template: None,
+            incident_description_max_length: 1000,
+            per_host_rate: None,
+            services_file: "services.json".to_string(),
+            services_source: None,
+            services_dir: None,
+            services_cache_file: None,
+            heartbeat_url: None,
+            webhook_url: None,
+            pagerduty_routing_key: None,
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+        };
+
+        router(ApiState {
+            db_pool: DbPool::new(&config).await.unwrap(),
+            last_cycle: LastCycle::default(),
+            check_interval: Duration::from_secs(60),
+            metrics: Metrics::default(),
+            cache: ServiceCache::default(),
+            observers: Arc::new(Vec::new()),
+            service_states: ServiceStates::default(),
+            http_clients: HttpClients::default(),
+            display_timezone: config.display_timezone,
+            config: Arc::new(config),
+        })
+    }
+
+    async fn status(router: &Router, method: &str, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn mutating_endpoints_need_the_admin_token() {
+        let router = test_router(Some("s3cret"), true).await;
+
+        assert_eq!(status(&router, "POST", "/incidents", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, "POST", "/incidents", Some("wrong")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, "POST", "/incidents", Some("s3cre")).await, StatusCode::UNAUTHORIZED);
+        // Past the check the request reaches the handler, which wants a JSON body.
+        assert_eq!(status(&router, "POST", "/incidents", Some("s3cret")).await, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(status(&router, "GET", "/version", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn mutating_endpoints_are_disabled_without_a_token() {
+        let router = test_router(None, true).await;
+
+        assert_eq!(status(&router, "POST", "/incidents", None).await, StatusCode::NOT_FOUND);
+        assert_eq!(status(&router, "POST", "/incidents", Some("anything")).await, StatusCode::NOT_FOUND);
+        assert_eq!(status(&router, "GET", "/metrics", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn private_reads_need_the_admin_token() {
+        let router = test_router(Some("s3cret"), false).await;
+
+        assert_eq!(status(&router, "GET", "/version", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, "GET", "/metrics", Some("wrong")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, "GET", "/version", Some("s3cret")).await, StatusCode::OK);
+        assert_eq!(status(&router, "GET", "/metrics", Some("s3cret")).await, StatusCode::OK);
+        // Probes don't carry the token.
+        assert_ne!(status(&router, "GET", "/healthz", None).await, StatusCode::UNAUTHORIZED);
+    }
+}
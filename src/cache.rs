@@ -0,0 +1,112 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::database::{FAILED_SAMPLE, Service};
+
+
+/// Raw results kept per service for `/services/{id}/recent`.
+pub const RECENT_RESULTS: usize = 100;
+
+
+/// What the read endpoints show for one service, refreshed after each of its checks.
+#[derive(Debug, Clone)]
+pub struct CachedService {
+    /// The service with its latest sample appended and `stats` filled in.
+    pub service: Service,
+    pub uptime_percentage: Option<f64>,
+    /// Whether a down or degraded incident is open.
+    pub has_open_incident: bool,
+}
+
+/// Outcome of a single check, kept as it was seen for debugging.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentResult {
+    pub timestamp: DateTime<Utc>,
+    /// `FAILED_SAMPLE` (-1) when down.
+    pub response_time_ms: i64,
+    pub status_code: Option<u16>,
+    pub failure_reason: Option<String>,
+    /// URL the failure was seen on, redacted.
+    pub failed_url: Option<String>,
+}
+
+impl RecentResult {
+    /// A cycle that recorded the service as down without a completed check.
+    pub fn unchecked(reason: &str) -> Self {
+        RecentResult {
+            timestamp: Utc::now(),
+            response_time_ms: FAILED_SAMPLE,
+            status_code: None,
+            failure_reason: Some(reason.to_string()),
+            failed_url: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    /// Services of the current cycle; the cache is complete once each has an entry.
+    service_ids: HashSet<String>,
+    services: BTreeMap<String, Arc<CachedService>>,
+    /// Latest results per service, oldest first and at most `RECENT_RESULTS`.
+    recent: HashMap<String, VecDeque<RecentResult>>,
+}
+
+/// Latest result of every service, kept by the monitoring loop so that the
+/// status page and `/services` don't query Postgres on each request. Entries
+/// are replaced whole, so readers never see a half-updated service.
+#[derive(Clone, Default)]
+pub struct ServiceCache(Arc<RwLock<CacheEntries>>);
+
+impl ServiceCache {
+    /// Starts a cycle over these services, dropping entries of removed ones.
+    pub fn set_services(&self, service_ids: HashSet<String>) {
+        let mut entries = self.0.write().unwrap_or_else(|e| e.into_inner());
+        entries.services.retain(|service_id, _| service_ids.contains(service_id));
+        entries.recent.retain(|service_id, _| service_ids.contains(service_id));
+        entries.service_ids = service_ids;
+    }
+
+    pub fn update(&self, cached: CachedService) {
+        let mut entries = self.0.write().unwrap_or_else(|e| e.into_inner());
+        entries.services.insert(cached.service.id.clone(), Arc::new(cached));
+    }
+
+    pub fn record(&self, service_id: &str, result: RecentResult) {
+        let mut entries = self.0.write().unwrap_or_else(|e| e.into_inner());
+        let results = entries.recent.entry(service_id.to_string()).or_default();
+        if results.len() >= RECENT_RESULTS {
+            results.pop_front();
+        }
+        results.push_back(result);
+    }
+
+    /// Up to `limit` of the service's latest results, newest first.
+    pub fn recent(&self, service_id: &str, limit: usize) -> Vec<RecentResult> {
+        let entries = self.0.read().unwrap_or_else(|e| e.into_inner());
+        entries.recent.get(service_id)
+            .map(|results| results.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The service's entry, `None` until it has been checked since startup.
+    pub fn service(&self, service_id: &str) -> Option<Arc<CachedService>> {
+        let entries = self.0.read().unwrap_or_else(|e| e.into_inner());
+        entries.services.get(service_id).cloned()
+    }
+
+    /// Every service, or `None` while some haven't been checked yet and the
+    /// caller has to read from the database instead.
+    pub fn services(&self) -> Option<Vec<Arc<CachedService>>> {
+        let entries = self.0.read().unwrap_or_else(|e| e.into_inner());
+        let complete = !entries.service_ids.is_empty()
+            && entries.service_ids.iter().all(|service_id| entries.services.contains_key(service_id));
+
+        complete.then(|| entries.services.values().cloned().collect())
+    }
+}
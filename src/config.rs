@@ -0,0 +1,275 @@
+use std::{
+    env,
+    str::FromStr,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::database::{
+    DEFAULT_INCIDENT_DESCRIPTION_MAX_LENGTH, DEFAULT_MAX_SAMPLES, DEFAULT_ONLINE_SAMPLES, MonitoringError,
+    ResponseTimeUnit,
+};
+use crate::monitor::CHECK_INTERVAL;
+use crate::request::{DEFAULT_USER_AGENT, validate_bind_address, validate_proxy};
+
+
+/// Settings read from the environment (and `.env`) once at startup.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database_host: String,
+    pub database_port: u16,
+    pub database_name: String,
+    pub database_user: String,
+    pub database_password: String,
+    pub api_address: SocketAddr,
+    /// Time zone of timestamps shown to people; stored and JSON times stay UTC.
+    pub display_timezone: Tz,
+    /// Bearer token required by every mutating API endpoint, which are
+    /// disabled when unset. `INCIDENT_TOKEN` is still read as a fallback.
+    pub admin_token: Option<String>,
+    /// Whether read endpoints answer without the admin token.
+    pub public_reads: bool,
+    /// Unix domain socket answering local status queries, disabled when unset.
+    pub ipc_socket: Option<String>,
+    /// OTLP collector that receives per-check spans and metrics with the `otel`
+    /// feature, disabled when unset.
+    pub otlp_endpoint: Option<String>,
+    pub user_agent: String,
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+    pub check_proxy: Option<String>,
+    /// Local address outgoing check connections are made from.
+    pub bind_address: Option<IpAddr>,
+    pub alert_cooldown: Option<Duration>,
+    /// Time after which a cycle stops waiting for unfinished checks.
+    pub cycle_deadline: Option<Duration>,
+    /// Consecutive failed checks after which a service is probed less often.
+    pub breaker_threshold: Option<i32>,
+    /// Most cycles skipped between probes while the breaker is open.
+    pub breaker_max_backoff: u32,
+    /// Serialize incident opening across replicas sharing the database.
+    pub coordinate_incidents: bool,
+    /// How often the services' DNS records and TTLs are recorded, disabled when unset.
+    pub dns_record_interval: Option<Duration>,
+    /// Samples kept per service unless the service sets its own `max_samples`.
+    pub max_samples: i32,
+    /// Recent samples whose majority sets a service's `is_online`.
+    pub online_samples: i32,
+    /// Unit response times are measured and stored in; stored samples are
+    /// converted at startup when it changes.
+    pub response_time_unit: ResponseTimeUnit,
+    /// Description of down incidents with `{service}`, `{status}`, `{failures}` and `{url}` placeholders.
+    pub incident_template: Option<String>,
+    /// Same for degraded-performance incidents.
+    pub degraded_incident_template: Option<String>,
+    /// Characters of an incident description stored, longer ones are truncated.
+    pub incident_description_max_length: usize,
+    /// Maximum checks per second sent to any one host.
+    pub per_host_rate: Option<f64>,
+    /// Local services file, read when `services_source` and `services_dir` are unset.
+    pub services_file: String,
+    pub services_source: Option<String>,
+    /// Directory whose `*.json` files are merged into the services, replacing `services_file`.
+    pub services_dir: Option<String>,
+    pub services_cache_file: Option<String>,
+    /// Dead man's switch pinged after every completed cycle.
+    pub heartbeat_url: Option<String>,
+    pub webhook_url: Option<String>,
+    pub pagerduty_routing_key: Option<String>,
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+}
+
+/// Collects every problem with the environment instead of stopping at the first.
+#[derive(Default)]
+struct EnvReader {
+    errors: Vec<String>,
+}
+
+impl EnvReader {
+    fn optional(&self, name: &str) -> Option<String> {
+        env::var(name).ok().filter(|value| !value.is_empty())
+    }
+
+    fn required(&mut self, name: &str) -> String {
+        self.optional(name).unwrap_or_else(|| {
+            self.errors.push(format!("{} must be set", name));
+            String::new()
+        })
+    }
+
+    fn parsed<T: FromStr>(&mut self, name: &str, description: &str) -> Option<T> {
+        let value = self.optional(name)?;
+        match value.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                self.errors.push(format!("{} must be {}, got {:?}", name, description, value));
+                None
+            }
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, MonitoringError> {
+        let mut reader = EnvReader::default();
+
+        let database_host = reader.required("DATABASE_HOST");
+        let database_port = reader.required("DATABASE_PORT");
+        let database_port = database_port.parse::<u16>().unwrap_or_else(|_| {
+            if !database_port.is_empty() {
+                reader.errors.push(format!("DATABASE_PORT must be a valid port number, got {:?}", database_port));
+            }
+            0
+        });
+
+        let check_proxy = reader.optional("CHECK_PROXY");
+        if let Some(Err(e)) = check_proxy.as_deref().map(|proxy| validate_proxy(proxy, &[])) {
+            reader.errors.push(format!("CHECK_PROXY is invalid: {}", e));
+        }
+
+        let bind_address: Option<IpAddr> = reader.parsed("BIND_ADDRESS", "an IP address");
+        if let Some(Err(e)) = bind_address.map(validate_bind_address) {
+            reader.errors.push(format!("BIND_ADDRESS is invalid: {}", e));
+        }
+
+        let admin_token = reader.optional("ADMIN_TOKEN").or_else(|| reader.optional("INCIDENT_TOKEN"));
+        let public_reads = reader.parsed("PUBLIC_READS", "true or false").unwrap_or(true);
+        if !public_reads && admin_token.is_none() {
+            reader.errors.push("PUBLIC_READS=false needs ADMIN_TOKEN to be set".to_string());
+        }
+
+        let services_source = reader.optional("SERVICES_SOURCE");
+        let services_dir = reader.optional("SERVICES_DIR");
+        if services_source.is_some() && services_dir.is_some() {
+            reader.errors.push("SERVICES_SOURCE and SERVICES_DIR can't be set together".to_string());
+        }
+
+        let client_cert_path = reader.optional("CLIENT_CERT_PATH");
+        let client_key_path = reader.optional("CLIENT_KEY_PATH");
+        if client_cert_path.is_some() != client_key_path.is_some() {
+            reader.errors.push("CLIENT_CERT_PATH and CLIENT_KEY_PATH must be set together".to_string());
+        }
+
+        let telegram_bot_token = reader.optional("TELEGRAM_BOT_TOKEN");
+        let telegram_chat_id = reader.optional("TELEGRAM_CHAT_ID");
+        if telegram_bot_token.is_some() != telegram_chat_id.is_some() {
+            reader.errors.push("TELEGRAM_BOT_TOKEN and TELEGRAM_CHAT_ID must be set together".to_string());
+        }
+
+        let per_host_rate = reader.parsed::<f64>("PER_HOST_RATE", "a number of requests per second");
+        if per_host_rate.is_some_and(|rate| rate <= 0.0 || !rate.is_finite()) {
+            reader.errors.push("PER_HOST_RATE must be greater than zero".to_string());
+        }
+
+        let breaker_threshold = reader.parsed::<i32>("BREAKER_THRESHOLD", "a number of failed checks");
+        if breaker_threshold.is_some_and(|threshold| threshold < 1) {
+            reader.errors.push("BREAKER_THRESHOLD must be at least 1".to_string());
+        }
+
+        let breaker_max_backoff = reader.parsed::<u32>("BREAKER_MAX_BACKOFF_CYCLES", "a number of cycles")
+            .unwrap_or(16);
+        if breaker_max_backoff < 1 {
+            reader.errors.push("BREAKER_MAX_BACKOFF_CYCLES must be at least 1".to_string());
+        }
+
+        let max_samples = reader.parsed::<i32>("MAX_SAMPLES", "a number of samples")
+            .unwrap_or(DEFAULT_MAX_SAMPLES);
+        if max_samples < 1 {
+            reader.errors.push("MAX_SAMPLES must be at least 1".to_string());
+        }
+
+        let dns_record_interval = reader.parsed::<u64>("DNS_RECORD_INTERVAL_SECS", "a number of seconds")
+            .map(Duration::from_secs);
+        if dns_record_interval.is_some_and(|interval| interval < CHECK_INTERVAL) {
+            reader.errors.push(format!(
+                "DNS_RECORD_INTERVAL_SECS must be at least the check interval of {} seconds", CHECK_INTERVAL.as_secs()
+            ));
+        }
+
+        let online_samples = reader.parsed::<i32>("ONLINE_SAMPLES", "a number of samples")
+            .unwrap_or(DEFAULT_ONLINE_SAMPLES);
+        if online_samples < 1 {
+            reader.errors.push("ONLINE_SAMPLES must be at least 1".to_string());
+        }
+
+        let incident_description_max_length = reader
+            .parsed::<usize>("INCIDENT_DESCRIPTION_MAX_LENGTH", "a number of characters")
+            .unwrap_or(DEFAULT_INCIDENT_DESCRIPTION_MAX_LENGTH);
+        if incident_description_max_length < 1 {
+            reader.errors.push("INCIDENT_DESCRIPTION_MAX_LENGTH must be at least 1".to_string());
+        }
+
+        let config = Config {
+            database_host,
+            database_port,
+            database_name: reader.required("DATABASE_NAME"),
+            database_user: reader.required("DATABASE_USER"),
+            database_password: reader.required("DATABASE_PASSWORD"),
+            api_address: reader.parsed("API_ADDRESS", "a socket address like 0.0.0.0:8080")
+                .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 8080))),
+            display_timezone: reader.parsed("DISPLAY_TIMEZONE", "an IANA time zone like Europe/Berlin")
+                .unwrap_or(Tz::UTC),
+            admin_token,
+            public_reads,
+            ipc_socket: reader.optional("IPC_SOCKET"),
+            otlp_endpoint: reader.optional("OTEL_EXPORTER_OTLP_ENDPOINT"),
+            user_agent: reader.optional("USER_AGENT")
+                .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+            client_cert_path,
+            client_key_path,
+            check_proxy,
+            bind_address,
+            alert_cooldown: reader.parsed("ALERT_COOLDOWN_SECS", "a number of seconds")
+                .map(Duration::from_secs),
+            per_host_rate,
+            cycle_deadline: reader.parsed("CYCLE_DEADLINE_SECS", "a number of seconds")
+                .map(Duration::from_secs),
+            breaker_threshold,
+            breaker_max_backoff,
+            max_samples,
+            online_samples,
+            response_time_unit: reader.parsed("RESPONSE_TIME_UNIT", "ms or us").unwrap_or_default(),
+            coordinate_incidents: reader.parsed("COORDINATE_INCIDENTS", "true or false").unwrap_or(false),
+            dns_record_interval,
+            incident_template: reader.optional("INCIDENT_TEMPLATE"),
+            degraded_incident_template: reader.optional("DEGRADED_INCIDENT_TEMPLATE"),
+            incident_description_max_length,
+            services_file: reader.optional("SERVICES_FILE")
+                .unwrap_or_else(|| "services.json".to_string()),
+            services_source,
+            services_dir,
+            services_cache_file: reader.optional("SERVICES_CACHE_FILE"),
+            heartbeat_url: reader.optional("HEARTBEAT_URL"),
+            webhook_url: reader.optional("WEBHOOK_URL"),
+            pagerduty_routing_key: reader.optional("PAGERDUTY_ROUTING_KEY"),
+            telegram_bot_token,
+            telegram_chat_id,
+        };
+
+        if reader.errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(MonitoringError(format!("Invalid configuration:\n  - {}", reader.errors.join("\n  - "))))
+        }
+    }
+}
+
+/// Formats a timestamp for people, like `2024-05-01 14:30 CEST`.
+pub fn display_time(time: DateTime<Utc>, timezone: Tz) -> String {
+    time.with_timezone(&timezone).format("%Y-%m-%d %H:%M %Z").to_string()
+}
+
+/// Formats how long an incident lasted for people, like `45s`, `3h 5m` or `2d 4h`.
+pub fn display_duration(duration: chrono::Duration) -> String {
+    let total_minutes = duration.num_minutes();
+    match (total_minutes / 1440, total_minutes / 60 % 24, total_minutes % 60) {
+        (0, 0, 0) => format!("{}s", duration.num_seconds().max(0)),
+        (0, 0, minutes) => format!("{}m", minutes),
+        (0, hours, minutes) => format!("{}h {}m", hours, minutes),
+        (days, hours, _) => format!("{}d {}h", days, hours),
+    }
+}
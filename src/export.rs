@@ -0,0 +1,106 @@
+use std::{fmt::Write as _, fs};
+
+use serde_json::json;
+
+use chrono::{SecondsFormat, Utc};
+
+use crate::database::{DbPool, MonitoringError, estimate_sample_times};
+use crate::monitor::CHECK_INTERVAL;
+
+
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Options of the `export` command:
+/// `export --service <id> --format csv|json [--out file]`.
+pub struct ExportArgs {
+    pub service_id: String,
+    pub format: ExportFormat,
+    /// Written to stdout when unset.
+    pub out: Option<String>,
+}
+
+impl ExportArgs {
+    pub fn parse(args: &[String]) -> Result<Self, MonitoringError> {
+        let mut service_id = None;
+        let mut format = None;
+        let mut out = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().cloned()
+                .ok_or_else(|| MonitoringError(format!("{} needs a value", arg)));
+
+            match arg.as_str() {
+                "--service" => service_id = Some(value()?),
+                "--format" => format = Some(match value()?.as_str() {
+                    "csv" => ExportFormat::Csv,
+                    "json" => ExportFormat::Json,
+                    other => return Err(MonitoringError(format!("Unknown export format {}, use csv or json", other))),
+                }),
+                "--out" => out = Some(value()?),
+                other => return Err(MonitoringError(format!("Unknown export option {}", other))),
+            }
+        }
+
+        Ok(ExportArgs {
+            service_id: service_id.ok_or_else(|| MonitoringError("export needs --service <id>".to_string()))?,
+            format: format.unwrap_or(ExportFormat::Csv),
+            out,
+        })
+    }
+}
+
+/// Writes a service's recorded response times, oldest first, in the unit they
+/// are stored in, with the time each was taken. Samples recorded before their
+/// times were stored get one estimated from the check interval and are marked
+/// `estimated`; a response time of -1 means down.
+pub async fn export_history(db_pool: &DbPool, args: &ExportArgs) -> Result<(), MonitoringError> {
+    let service = db_pool.list_services().await?
+        .into_iter()
+        .find(|service| service.id == args.service_id)
+        .ok_or_else(|| MonitoringError(format!("No service with id {}", args.service_id)))?;
+
+    let history = db_pool.sample_history(&service.id).await?;
+    let times: Vec<_> = history.iter().map(|(_, time)| *time).collect();
+    let samples: Vec<_> = history.iter().map(|(response_time, _)| *response_time)
+        .zip(estimate_sample_times(&times, CHECK_INTERVAL, Utc::now()))
+        .map(|(response_time, (time, estimated))| (time.to_rfc3339_opts(SecondsFormat::Secs, true), estimated, response_time))
+        .collect();
+
+    let field = format!("response_time_{}", service.response_time_unit.as_str());
+    let output = match args.format {
+        ExportFormat::Csv => {
+            let mut csv = format!("timestamp,estimated,{}\n", field);
+            for (time, estimated, response_time) in &samples {
+                writeln!(csv, "{},{},{}", time, estimated, response_time).unwrap();
+            }
+            csv
+        }
+        ExportFormat::Json => {
+            let samples: Vec<_> = samples.iter()
+                .map(|(time, estimated, response_time)| json!({
+                    "timestamp": time,
+                    "estimated": estimated,
+                    field.as_str(): response_time,
+                }))
+                .collect();
+
+            json!({
+                "service_id": service.id,
+                "name": service.name,
+                "response_time_unit": service.response_time_unit,
+                "samples": samples,
+            }).to_string()
+        }
+    };
+
+    match &args.out {
+        Some(path) => fs::write(path, output)?,
+        None => print!("{}", output),
+    }
+
+    Ok(())
+}
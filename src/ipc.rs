@@ -0,0 +1,58 @@
+use std::{fs, io::ErrorKind};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+
+use crate::api::{ApiState, current_services};
+use crate::database::MonitoringError;
+
+
+/// Serves local status queries on a Unix domain socket. Each request is one
+/// line, `GET services` or `GET incidents`, answered with one line of JSON.
+pub async fn serve(path: &str, state: ApiState) -> Result<(), MonitoringError> {
+    // A socket file left behind by an earlier run would make the bind fail.
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let listener = UnixListener::bind(path)?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &state).await {
+                eprintln!("Error serving IPC connection: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: UnixStream, state: &ApiState) -> Result<(), MonitoringError> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match answer(line.trim(), state).await {
+            Ok(response) => response,
+            Err(e) => json!({ "error": e.0 }),
+        };
+
+        writer.write_all(format!("{}\n", response).as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+async fn answer(request: &str, state: &ApiState) -> Result<Value, MonitoringError> {
+    match request.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["GET", "services"] => Ok(json!(current_services(state, None).await?)),
+        ["GET", "incidents"] => Ok(json!(state.db_pool.list_incidents(false).await?)),
+        _ => Err(MonitoringError(format!(
+            "Unknown request {:?}, expected GET services or GET incidents", request
+        ))),
+    }
+}
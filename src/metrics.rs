@@ -0,0 +1,77 @@
+use std::{
+    fmt::Write,
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::database::FAILED_SAMPLE;
+
+pub const RESPONSE_TIME_BUCKETS_MS: [i64; 10] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+#[derive(Default)]
+struct ServiceMetrics {
+    up: bool,
+    bucket_counts: [u64; RESPONSE_TIME_BUCKETS_MS.len()],
+    sum: u64,
+    count: u64,
+}
+
+/// Prometheus metrics collected from check results, rendered by `/metrics`.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    services: Arc<Mutex<BTreeMap<String, ServiceMetrics>>>,
+}
+
+impl Metrics {
+    /// Records one check result. Downtime (`FAILED_SAMPLE`) only updates
+    /// the `up` gauge so that it doesn't skew the latency histogram.
+    pub fn observe(&self, service_id: &str, response_time: i64) {
+        let mut services = self.services.lock().unwrap();
+        let metrics = services.entry(service_id.to_string()).or_default();
+
+        metrics.up = response_time != FAILED_SAMPLE;
+        if !metrics.up {
+            return;
+        }
+
+        for (bucket, count) in RESPONSE_TIME_BUCKETS_MS.iter().zip(metrics.bucket_counts.iter_mut()) {
+            if response_time <= *bucket {
+                *count += 1;
+            }
+        }
+        metrics.sum += response_time as u64;
+        metrics.count += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let services = self.services.lock().unwrap();
+        let mut output = String::new();
+
+        output.push_str("# HELP statussentinel_up Whether the service passed its last check.\n");
+        output.push_str("# TYPE statussentinel_up gauge\n");
+        for (service_id, metrics) in services.iter() {
+            writeln!(output, "statussentinel_up{{service=\"{}\"}} {}", service_id, metrics.up as u8).unwrap();
+        }
+
+        output.push_str("# HELP statussentinel_response_time_ms Response time of successful checks in milliseconds.\n");
+        output.push_str("# TYPE statussentinel_response_time_ms histogram\n");
+        for (service_id, metrics) in services.iter() {
+            for (bucket, count) in RESPONSE_TIME_BUCKETS_MS.iter().zip(metrics.bucket_counts.iter()) {
+                writeln!(
+                    output,
+                    "statussentinel_response_time_ms_bucket{{service=\"{}\",le=\"{}\"}} {}",
+                    service_id, bucket, count
+                ).unwrap();
+            }
+            writeln!(
+                output,
+                "statussentinel_response_time_ms_bucket{{service=\"{}\",le=\"+Inf\"}} {}",
+                service_id, metrics.count
+            ).unwrap();
+            writeln!(output, "statussentinel_response_time_ms_sum{{service=\"{}\"}} {}", service_id, metrics.sum).unwrap();
+            writeln!(output, "statussentinel_response_time_ms_count{{service=\"{}\"}} {}", service_id, metrics.count).unwrap();
+        }
+
+        output
+    }
+}
@@ -0,0 +1,1423 @@
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use chrono::Utc;
+use futures::future::join_all;
+use reqwest::{Method, StatusCode, Url};
+use serde_json::{json, Value};
+use tokio::{sync::Mutex, task::{JoinError, JoinHandle, spawn_blocking}, time::{sleep, timeout_at}};
+
+use crate::api::LastCycle;
+use crate::cache::{CachedService, RecentResult, ServiceCache};
+use crate::config::{Config, display_time};
+use crate::database::{
+    AnomalyDetection, Aggregation, DbPool, Detection, DnsRecord, EXTERNAL_INCIDENT_SOURCE, FAILED_SAMPLE, Incident, MonitoringError,
+    ResponseTimeUnit, Service, Severity,
+};
+use crate::metrics::Metrics;
+use crate::notify::{
+    IpChange, LatencyBreach, Observers, notify_close, notify_ip_change, notify_latency_breach, notify_open, notify_reminder,
+};
+use crate::ocsp::{RevocationStatus, revocation_status};
+use crate::request::{
+    CheckOptions, DEFAULT_RETRY_ON, DbKind, FailureClass, HttpClients, LEARN_SHA256, PhaseTimings, ResponseResult, describe_error,
+    get_db_response_time, get_icmp_response_time, get_journey_response_time, get_minecraft_response_time, get_request_response_time,
+    get_sftp_response_time, redact_url, resolve_addresses, resolve_minecraft_address, send_heartbeat, wait_for_host_slot,
+};
+use crate::telemetry::instrument_check;
+
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const DB_RETRY_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const DB_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Pause before retrying a failed HTTP check.
+const CHECK_RETRY_DELAY: Duration = Duration::from_secs(1);
+pub const MAX_CHECK_RETRIES: i32 = 10;
+
+#[derive(Clone, Default)]
+pub struct ServiceState {
+    pub has_open_incident: bool,
+    pub consecutive_successes: i32,
+    pub has_degraded_incident: bool,
+    /// Consecutive successful checks slower than the degraded threshold.
+    pub consecutive_slow: i32,
+    /// Consecutive successful checks within the degraded threshold.
+    pub consecutive_fast: i32,
+    /// When observers were last told about the open incident, used to space out reminders.
+    pub last_notified: Option<Instant>,
+    /// Consecutive failed checks, which trip the circuit breaker.
+    pub consecutive_failures: i32,
+    /// Cycles the breaker skips after the last failed check; doubles up to the configured cap.
+    pub backoff_cycles: u32,
+    /// Cycles still to skip before the service is probed again.
+    pub skip_cycles: u32,
+    pub has_anomaly_incident: bool,
+    /// Exponential moving average and variance of successful response times,
+    /// the baseline of `anomaly` detection. `None` before the first success.
+    pub latency_ema: Option<f64>,
+    pub latency_variance: f64,
+    /// Successful samples averaged so far, counted up to the warmup.
+    pub latency_samples: u32,
+    /// Consecutive successful checks above the anomaly threshold.
+    pub consecutive_anomalous: i32,
+    /// Consecutive successful checks within the anomaly threshold.
+    pub consecutive_usual: i32,
+    /// When the last `alert_on_single_breach_ms` notification went out. Without
+    /// an alert cooldown it's cleared by the next check within the threshold.
+    pub last_breach_notified: Option<Instant>,
+}
+
+pub type ServiceStates = Arc<Mutex<HashMap<String, ServiceState>>>;
+
+/// Builds the per-service state from the database so that incidents left open
+/// by a previous run are picked up instead of being opened a second time.
+pub async fn load_service_states(db_pool: &DbPool) -> Result<ServiceStates, MonitoringError> {
+    let mut open_incidents = Vec::new();
+    for severity in [Severity::Down, Severity::Degraded, Severity::Anomaly] {
+        open_incidents.push((severity, db_pool.list_open_incident_service_ids(severity).await?));
+    }
+
+    let service_states: HashMap<String, ServiceState> = db_pool.list_services().await?
+        .into_iter()
+        .map(|service| {
+            let open_severities: Vec<Severity> = open_incidents.iter()
+                .filter(|(_, service_ids)| service_ids.contains(&service.id))
+                .map(|&(severity, _)| severity)
+                .collect();
+            let state = restored_state(&open_severities, service.latency_ema, service.latency_variance, &service.response_times);
+            (service.id, state)
+        })
+        .collect();
+
+    Ok(Arc::new(Mutex::new(service_states)))
+}
+
+/// The state a service resumes with after a restart: the incidents still open
+/// in the database and the stored latency baseline.
+fn restored_state(
+    open_severities: &[Severity],
+    latency_ema: Option<f64>,
+    latency_variance: Option<f64>,
+    response_times: &[i64],
+) -> ServiceState {
+    let has_open_incident = open_severities.contains(&Severity::Down);
+    // The stored baseline was averaged over the successful samples still on record.
+    let latency_samples = match latency_ema {
+        Some(_) => response_times.iter().filter(|&&response_time| response_time != FAILED_SAMPLE).count() as u32,
+        None => 0,
+    };
+
+    ServiceState {
+        has_open_incident,
+        has_degraded_incident: open_severities.contains(&Severity::Degraded),
+        has_anomaly_incident: open_severities.contains(&Severity::Anomaly),
+        last_notified: has_open_incident.then(Instant::now),
+        latency_ema,
+        latency_variance: latency_variance.unwrap_or(0.0),
+        latency_samples,
+        ..Default::default()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn run_monitoring_loop(
+    db_pool: &DbPool,
+    service_states: &ServiceStates,
+    last_cycle: &LastCycle,
+    metrics: &Metrics,
+    cache: &ServiceCache,
+    observers: &Arc<Observers>,
+    http_clients: &HttpClients,
+    config: &Config,
+) -> Result<(), MonitoringError> {
+    // Set while the database is unreachable; the wait doubles with each failed retry.
+    let mut db_backoff: Option<Duration> = None;
+
+    loop {
+        // Per-service errors are handled inside the cycle, so a failed cycle
+        // means the services couldn't be loaded from the database.
+        if let Err(e) = run_once(db_pool, service_states, metrics, cache, observers, http_clients, config).await {
+            let backoff = match db_backoff {
+                None => {
+                    eprintln!("Error loading services, retrying until the database is back: {}", e);
+                    DB_RETRY_MIN_BACKOFF
+                }
+                Some(backoff) => (backoff * 2).min(DB_RETRY_MAX_BACKOFF),
+            };
+            db_backoff = Some(backoff);
+            sleep(backoff).await;
+            continue;
+        }
+
+        if db_backoff.take().is_some() {
+            println!("*  Database connection restored, checks resumed.");
+        }
+
+        last_cycle.mark();
+
+        // Sent in the background so that a slow heartbeat endpoint doesn't delay the next cycle.
+        let config = config.clone();
+        tokio::spawn(async move { heartbeat(&config).await });
+
+        sleep(CHECK_INTERVAL).await;
+    }
+}
+
+/// Tells the external monitor at `HEARTBEAT_URL`, if any, that a cycle completed.
+/// Failures are only logged; the external monitor alerts when heartbeats stop.
+pub async fn heartbeat(config: &Config) {
+    if let Some(heartbeat_url) = &config.heartbeat_url {
+        if let Err(e) = send_heartbeat(heartbeat_url, &config.user_agent).await {
+            eprintln!("Error sending heartbeat: {}", e);
+        }
+    }
+}
+
+/// Resolves the hostnames of every HTTP service once per `interval` and stores
+/// their A and AAAA records with TTLs. Purely diagnostic: checks don't use them.
+pub async fn run_dns_recording(db_pool: &DbPool, interval: Duration) {
+    loop {
+        match db_pool.list_services().await {
+            Ok(services) => {
+                for service in &services {
+                    record_dns(db_pool, service).await;
+                }
+            }
+            Err(e) => eprintln!("Error loading services for DNS recording: {}", e),
+        }
+
+        sleep(interval).await;
+    }
+}
+
+async fn record_dns(db_pool: &DbPool, service: &Service) {
+    let hostnames: BTreeSet<String> = service.check_urls().iter()
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+        .filter_map(|url| Url::parse(url).ok()?.domain().map(str::to_string))
+        .collect();
+
+    let resolved_at = Utc::now();
+    let mut records = Vec::new();
+    for hostname in hostnames {
+        match resolve_addresses(&hostname).await {
+            Ok(addresses) => records.extend(addresses.into_iter().map(|(record_type, address, ttl)| DnsRecord {
+                hostname: hostname.clone(),
+                record_type: record_type.to_string(),
+                address: address.to_string(),
+                ttl_secs: ttl.min(i32::MAX as u32) as i32,
+                resolved_at,
+            })),
+            Err(e) => eprintln!("Error resolving {} for {}: {}", hostname, service.name, e),
+        }
+    }
+
+    if records.is_empty() {
+        return;
+    }
+    if let Err(e) = db_pool.add_dns_records(&service.id, &records).await {
+        eprintln!("Error adding DNS records for {}: {}", service.name, e);
+    }
+}
+
+/// Resolves the hostnames of a service with `alert_on_ip_change` and notifies
+/// when their addresses differ from the last stored ones. Failed lookups are
+/// logged and leave the stored addresses alone.
+async fn watch_addresses(db_pool: &DbPool, observers: &Observers, channels: &[String], service: &Service) {
+    let hostnames: BTreeSet<String> = service.check_urls().iter()
+        .filter_map(|url| Url::parse(url).ok()?.domain().map(str::to_string))
+        .collect();
+
+    for hostname in hostnames {
+        let mut addresses: Vec<String> = match resolve_addresses(&hostname).await {
+            Ok(addresses) => addresses.into_iter().map(|(_, address, _)| address.to_string()).collect(),
+            Err(e) => {
+                eprintln!("Error resolving {} for {}: {}", hostname, service.name, e);
+                continue;
+            }
+        };
+        addresses.sort();
+        addresses.dedup();
+        if addresses.is_empty() {
+            continue;
+        }
+
+        let old_addresses = match db_pool.update_service_ips(&service.id, &hostname, &addresses).await {
+            Ok(Some(old_addresses)) => old_addresses,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("Error storing the addresses of {} for {}: {}", hostname, service.name, e);
+                continue;
+            }
+        };
+
+        println!(
+            "{} of {} now resolves to {}, was {}",
+            hostname, service.name, addresses.join(", "), old_addresses.join(", "),
+        );
+        let change = IpChange {
+            service_id: service.id.clone(),
+            service_name: service.name.clone(),
+            hostname,
+            old_addresses,
+            new_addresses: addresses,
+            timestamp: Utc::now(),
+        };
+        notify_ip_change(observers, channels, &change).await;
+    }
+}
+
+async fn set_open_incident(service_states: &ServiceStates, service_id: &str, severity: Severity, is_open: bool) {
+    if let Some(state) = service_states.lock().await.get_mut(service_id) {
+        match severity {
+            Severity::Down => {
+                state.has_open_incident = is_open;
+                state.last_notified = is_open.then(Instant::now);
+            }
+            Severity::Degraded => state.has_degraded_incident = is_open,
+            Severity::Anomaly => state.has_anomaly_incident = is_open,
+        }
+    }
+}
+
+/// Opens an incident, or with `coordinate` returns `None` when another replica
+/// already has one of the same severity open for the service.
+async fn open_incident(
+    db_pool: &DbPool,
+    coordinate: bool,
+    service_id: &str,
+    description: &str,
+    severity: Severity,
+    metadata: Value,
+) -> Result<Option<Incident>, MonitoringError> {
+    if coordinate {
+        db_pool.add_incident_exclusive(service_id, description, severity, metadata).await
+    } else {
+        db_pool.add_incident(service_id, description, severity, metadata).await.map(Some)
+    }
+}
+
+/// Ends the service's open incidents of one severity and notifies observers.
+/// Incidents reported through the API stay open until they are resolved there.
+/// With `coordinate` they are ended under the service's advisory lock.
+async fn close_incidents(
+    db_pool: &DbPool,
+    coordinate: bool,
+    service_states: &ServiceStates,
+    observers: &Observers,
+    channels: &[String],
+    service_id: &str,
+    severity: Severity,
+) {
+    let mut closed_incidents = Vec::new();
+    if coordinate {
+        match db_pool.end_incidents_exclusive(service_id, severity).await {
+            Ok(incidents) => closed_incidents = incidents,
+            Err(e) => eprintln!("Error ending incidents of {}: {}", service_id, e),
+        }
+    } else if let Ok(incidents) = db_pool.list_incidents(false).await {
+        for incident in incidents {
+            let external = incident.metadata["source"] == EXTERNAL_INCIDENT_SOURCE;
+            if incident.service_id == service_id && incident.severity == severity && !external {
+                if let Ok(Some(incident)) = db_pool.end_incident(incident.id).await {
+                    closed_incidents.push(incident);
+                }
+            }
+        }
+    }
+    set_open_incident(service_states, service_id, severity, false).await;
+
+    for incident in &closed_incidents {
+        notify_close(observers, channels, incident).await;
+    }
+}
+
+/// Fills `{service}`, `{status}`, `{failures}` and `{url}` in an incident
+/// template. Unknown placeholders are kept as they are, and substituted values
+/// are not scanned again.
+fn render_incident_template(template: &str, service: &str, status: &str, failures: i32, url: &str) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let placeholder = rest.find('}').map(|end| &rest[..=end]);
+        let value = match placeholder {
+            Some("{service}") => service.to_string(),
+            Some("{status}") => status.to_string(),
+            Some("{failures}") => failures.to_string(),
+            Some("{url}") => url.to_string(),
+            _ => {
+                rendered.push('{');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        rendered.push_str(&value);
+        rest = &rest[placeholder.map_or(0, str::len)..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Result of checking a single URL, down unless built with `up`.
+#[derive(Debug)]
+struct CheckOutcome {
+    /// `FAILED_SAMPLE` when down.
+    response_time: i64,
+    /// Why the check failed, when known.
+    failure_reason: Option<String>,
+    /// URL the failure was seen on, redacted.
+    failed_url: Option<String>,
+    /// Status of an HTTP response that failed the check.
+    status_code: Option<u16>,
+    /// Checksum of a body that didn't match the `expected_sha256`.
+    body_sha256: Option<String>,
+    /// Whether the endpoint answered at all, even if the check then failed.
+    reached: bool,
+}
+
+impl Default for CheckOutcome {
+    fn default() -> Self {
+        CheckOutcome {
+            response_time: FAILED_SAMPLE, failure_reason: None, failed_url: None, status_code: None, body_sha256: None,
+            reached: false,
+        }
+    }
+}
+
+impl CheckOutcome {
+    fn up(response_time: i64) -> Self {
+        CheckOutcome { response_time, reached: true, ..Default::default() }
+    }
+
+    fn down(url: &str, reason: Option<String>) -> Self {
+        CheckOutcome { failure_reason: reason, failed_url: Some(redact_url(url)), ..Default::default() }
+    }
+
+    /// Outcome of a non-HTTP probe, which fails with its reason.
+    fn probed(url: &str, response_time: Result<i64, String>) -> Self {
+        match response_time {
+            Ok(response_time) => CheckOutcome::up(response_time),
+            Err(reason) => CheckOutcome::down(url, Some(reason)),
+        }
+    }
+
+    /// Outcome of an HTTP check that got a response.
+    fn from_response(url: &str, result: ResponseResult) -> Self {
+        let outcome = match result {
+            ResponseResult::Success(time) => CheckOutcome::up(time),
+            ResponseResult::StatusError(status) => CheckOutcome {
+                status_code: Some(status.as_u16()),
+                ..CheckOutcome::down(url, Some(format!("HTTP {} error", status.as_str())))
+            },
+            ResponseResult::ContentMismatch(reason) | ResponseResult::CorsMismatch(reason) | ResponseResult::AssertionFailed(reason) => {
+                CheckOutcome::down(url, Some(reason))
+            }
+            ResponseResult::ChecksumMismatch { expected, actual } => CheckOutcome {
+                failure_reason: Some(format!("response body SHA-256 is {}, expected {}", actual, expected)),
+                body_sha256: Some(actual),
+                ..CheckOutcome::down(url, None)
+            },
+        };
+        CheckOutcome { reached: true, ..outcome }
+    }
+
+    /// Reinterprets the check of an `invert` service, which should be
+    /// unreachable. Any response, even an error status or one failing the
+    /// content checks, means it was reached; a check that got no answer,
+    /// errors included, is recorded as up in 0ms.
+    fn inverted(checked: Result<Self, MonitoringError>, url: &str) -> Result<Self, MonitoringError> {
+        let reached = checked.as_ref().is_ok_and(|outcome| outcome.reached);
+        if !reached {
+            return Ok(CheckOutcome::up(0));
+        }
+
+        let status_code = checked.ok().and_then(|outcome| outcome.status_code);
+        let reason = match status_code {
+            Some(status_code) => format!("{} is unexpectedly reachable (HTTP {})", redact_url(url), status_code),
+            None => format!("{} is unexpectedly reachable", redact_url(url)),
+        };
+        Ok(CheckOutcome { status_code, ..CheckOutcome::down(url, Some(reason)) })
+    }
+}
+
+/// Names the kind of check a URL gets, as reported in incident metadata.
+fn check_type(url: &str) -> &'static str {
+    if url.starts_with("mc://") {
+        "minecraft"
+    } else if url.starts_with("ping://") {
+        "icmp"
+    } else if url.starts_with("sftp://") {
+        "sftp"
+    } else if let Some(kind) = DbKind::from_url(url) {
+        kind.as_str()
+    } else {
+        "http"
+    }
+}
+
+/// Incident changes decided for one check result.
+#[derive(Default)]
+pub struct Transitions {
+    pub open: bool,
+    pub close: bool,
+    pub remind: bool,
+    pub open_degraded: bool,
+    pub close_degraded: bool,
+    pub open_anomaly: bool,
+    pub close_anomaly: bool,
+}
+
+impl Transitions {
+    /// Keeps incidents from opening while closing the ones already open.
+    pub fn suppress_opens(&mut self) {
+        self.open = false;
+        self.open_degraded = false;
+        self.open_anomaly = false;
+    }
+}
+
+/// A service's settings for turning samples into incidents.
+#[derive(Debug, Clone, Copy)]
+pub struct DetectionRules {
+    pub detection: Detection,
+    pub failure_rate: i32,
+    pub failure_window: i32,
+    pub recovery_threshold: i32,
+    pub degraded_threshold_ms: Option<i32>,
+    pub degraded_cycles: i32,
+    pub anomaly: Option<AnomalyDetection>,
+    pub min_samples: Option<i32>,
+    /// Unit of the samples, which `degraded_threshold_ms` is converted to.
+    pub response_time_unit: ResponseTimeUnit,
+}
+
+impl From<&Service> for DetectionRules {
+    fn from(service: &Service) -> Self {
+        DetectionRules {
+            detection: service.detection,
+            failure_rate: service.failure_rate,
+            failure_window: service.failure_window,
+            recovery_threshold: service.recovery_threshold,
+            degraded_threshold_ms: service.degraded_threshold_ms,
+            degraded_cycles: service.degraded_cycles,
+            anomaly: service.anomaly,
+            min_samples: service.min_samples,
+            response_time_unit: service.response_time_unit,
+        }
+    }
+}
+
+impl DetectionRules {
+    /// Number of most recent samples whose failures are counted.
+    pub fn window(&self) -> i32 {
+        match self.detection {
+            Detection::Consecutive => 5,
+            Detection::Rate => self.failure_window,
+        }
+    }
+
+    /// Whether a service with this many samples since it was added is still too
+    /// new to open incidents. The grace lasts `min_samples`, or `window()` checks.
+    pub fn in_grace(&self, samples: usize) -> bool {
+        (samples as i64) < i64::from(self.min_samples.unwrap_or_else(|| self.window()))
+    }
+
+    /// Whether this many failures among the last `window()` samples open an incident.
+    pub fn is_failing(&self, recent_failures: i32) -> bool {
+        match self.detection {
+            Detection::Consecutive => recent_failures >= 5,
+            // Taken over the full window, so a new service needs that many samples first.
+            Detection::Rate => recent_failures * 100 > self.failure_rate * self.window(),
+        }
+    }
+}
+
+/// Counts one sample into the state and decides which incidents it opens or
+/// closes. Only the counters change; reminders, the circuit breaker and the
+/// open-incident flags are left to the caller, so replays can use it as well.
+pub fn advance_state(state: &mut ServiceState, rules: &DetectionRules, response_time: i64, failing: bool) -> Transitions {
+    if response_time == FAILED_SAMPLE {
+        state.consecutive_successes = 0;
+        state.consecutive_slow = 0;
+        state.consecutive_fast = 0;
+        state.consecutive_anomalous = 0;
+        state.consecutive_usual = 0;
+        state.consecutive_failures += 1;
+
+        return Transitions {
+            open: failing && !state.has_open_incident,
+            ..Default::default()
+        };
+    }
+
+    state.consecutive_successes += 1;
+    state.consecutive_failures = 0;
+    state.backoff_cycles = 0;
+
+    let is_slow = rules.degraded_threshold_ms
+        .is_some_and(|threshold| response_time > rules.response_time_unit.ms_to_sample(i64::from(threshold)));
+    if is_slow {
+        state.consecutive_slow += 1;
+        state.consecutive_fast = 0;
+    } else {
+        state.consecutive_slow = 0;
+        state.consecutive_fast += 1;
+    }
+
+    let is_anomalous = rules.anomaly.is_some_and(|anomaly| update_latency_baseline(state, &anomaly, response_time));
+    if is_anomalous {
+        state.consecutive_anomalous += 1;
+        state.consecutive_usual = 0;
+    } else {
+        state.consecutive_anomalous = 0;
+        state.consecutive_usual += 1;
+    }
+
+    Transitions {
+        close: state.has_open_incident && state.consecutive_successes >= rules.recovery_threshold,
+        open_degraded: !state.has_degraded_incident && state.consecutive_slow >= rules.degraded_cycles,
+        close_degraded: state.has_degraded_incident && state.consecutive_fast >= rules.recovery_threshold,
+        open_anomaly: rules.anomaly.is_some_and(|anomaly| {
+            !state.has_anomaly_incident && state.consecutive_anomalous >= anomaly.cycles
+        }),
+        close_anomaly: state.has_anomaly_incident && state.consecutive_usual >= rules.recovery_threshold,
+        ..Default::default()
+    }
+}
+
+/// Folds a successful response time into the moving average and variance and
+/// returns whether it was anomalous. Anomalous samples are left out, since a
+/// single spike would otherwise widen the variance enough to hide the next ones.
+fn update_latency_baseline(state: &mut ServiceState, anomaly: &AnomalyDetection, response_time: i64) -> bool {
+    let sample = response_time as f64;
+    let Some(ema) = state.latency_ema else {
+        state.latency_ema = Some(sample);
+        state.latency_variance = 0.0;
+        state.latency_samples = 1;
+        return false;
+    };
+
+    let threshold = ema + anomaly.k * state.latency_variance.sqrt();
+    if state.latency_samples >= anomaly.warmup() && sample > threshold {
+        return true;
+    }
+
+    let difference = sample - ema;
+    let increment = anomaly.alpha * difference;
+    state.latency_ema = Some(ema + increment);
+    state.latency_variance = (1.0 - anomaly.alpha) * (state.latency_variance + difference * increment);
+    state.latency_samples = state.latency_samples.saturating_add(1);
+
+    false
+}
+
+fn max_samples(service: &Service, config: &Config) -> i32 {
+    service.max_samples.unwrap_or(config.max_samples)
+}
+
+/// Combines the global check settings with the service's own overrides.
+fn check_options(service: &Service, config: &Config) -> CheckOptions {
+    let (client_cert_path, client_key_path) = match (&service.client_cert_path, &service.client_key_path) {
+        (Some(cert_path), Some(key_path)) => (Some(cert_path.clone()), Some(key_path.clone())),
+        _ => (config.client_cert_path.clone(), config.client_key_path.clone()),
+    };
+
+    CheckOptions {
+        user_agent: service.user_agent.clone()
+            .unwrap_or_else(|| config.user_agent.clone()),
+        method: service.learned_method.as_deref().or(service.method.as_deref())
+            .and_then(|method| Method::from_bytes(method.to_uppercase().as_bytes()).ok())
+            .unwrap_or(Method::GET),
+        json_pointer: service.json_pointer.clone(),
+        json_expected: service.json_expected.clone(),
+        client_cert_path,
+        client_key_path,
+        proxy: service.proxy.clone().or_else(|| config.check_proxy.clone()),
+        bypass_proxy: service.bypass_proxy,
+        per_host_rate: config.per_host_rate,
+        resolve_override: service.resolve_override.clone(),
+        bearer_token: service.bearer_token.clone(),
+        verify_tls: service.verify_tls,
+        host_header: service.host_header.clone(),
+        minecraft_protocol: service.minecraft_protocol,
+        pre_request: service.pre_request.clone(),
+        body: service.body.clone(),
+        content_type: service.content_type.clone(),
+        expected_headers: service.expected_headers.clone(),
+        expected_content_type: service.expected_content_type.clone(),
+        concurrency: service.concurrency,
+        concurrency_aggregation: service.concurrency_aggregation,
+        sftp: service.sftp.clone(),
+        expected_redirect_location: service.expected_redirect_location.clone(),
+        cors: service.cors.clone(),
+        retries: service.retries.max(0) as u32,
+        retry_on: service.retry_on.clone().unwrap_or_else(|| DEFAULT_RETRY_ON.to_vec()),
+        assertions: service.assertions.clone(),
+        assertion_policy: service.assertion_policy,
+        ocsp: service.ocsp,
+        bind_address: service.bind_address.or(config.bind_address),
+        journey: service.journey.clone(),
+        // Until a baseline is learned the check only records the body's checksum.
+        expected_sha256: match service.expected_sha256.as_deref() {
+            Some(LEARN_SHA256) => Some(service.learned_sha256.clone().unwrap_or_else(|| LEARN_SHA256.to_string())),
+            expected => expected.map(str::to_string),
+        },
+        response_time_unit: service.response_time_unit,
+    }
+}
+
+/// Checks a single URL.
+async fn check_url(
+    url: &str,
+    options: &mut CheckOptions,
+    http_clients: &HttpClients,
+    name: &str,
+    service_id: &str,
+    db_pool: &DbPool,
+) -> Result<CheckOutcome, MonitoringError> {
+    if let Some(rate) = options.per_host_rate {
+        wait_for_host_slot(url, rate).await;
+    }
+
+    // A journey replaces the request to the service's own URL.
+    if !options.journey.is_empty() {
+        let completed = get_journey_response_time(&options.journey, options).await
+            .map_err(|e| MonitoringError(format!("Journey check for {} failed: {}", name, describe_error(e.as_ref()))))?;
+        return Ok(match completed {
+            Ok(response_time) => CheckOutcome::up(response_time),
+            Err(failure) => CheckOutcome::down(&failure.url, Some(failure.to_string())),
+        });
+    }
+
+    if let Some(server_addr) = url.strip_prefix("mc://") {
+        let (host, port) = resolve_minecraft_address(server_addr).await;
+        let (protocol, bind_address, unit) = (options.minecraft_protocol, options.bind_address, options.response_time_unit);
+        let response_time = spawn_blocking(move || {
+            get_minecraft_response_time(&host, port, protocol, bind_address, unit).map_err(|e| e.to_string())
+        }).await.map_err(|e| MonitoringError(e.to_string()))?;
+        return Ok(CheckOutcome::probed(url, response_time));
+    }
+
+    if let Some(host) = url.strip_prefix("ping://") {
+        let (host, bind_address, unit) = (host.to_string(), options.bind_address, options.response_time_unit);
+        let response_time = spawn_blocking(move || {
+            get_icmp_response_time(&host, bind_address, unit).map_err(|e| e.to_string())
+        }).await.map_err(|e| MonitoringError(e.to_string()))?
+            .map_err(|e| MonitoringError(format!("Ping check for {} failed: {}", name, e)))?;
+        return Ok(CheckOutcome::probed(url, response_time));
+    }
+
+    if url.starts_with("sftp://") {
+        let address = Url::parse(url).map_err(|e| MonitoringError(format!("Invalid SFTP URL for {}: {}", name, e)))?;
+        let host = address.host_str().unwrap_or_default().trim_matches(['[', ']']).to_string();
+        let port = address.port().unwrap_or(22);
+        let credentials = options.sftp.clone();
+        let (bind_address, unit) = (options.bind_address, options.response_time_unit);
+        let response_time = spawn_blocking(move || {
+            get_sftp_response_time(&host, port, credentials.as_ref(), bind_address, unit).map_err(|e| e.to_string())
+        }).await.map_err(|e| MonitoringError(e.to_string()))?;
+        return Ok(CheckOutcome::probed(url, response_time));
+    }
+
+    if let Some(kind) = DbKind::from_url(url) {
+        return Ok(match get_db_response_time(kind, url, options.response_time_unit).await {
+            Ok(response_time) => CheckOutcome::up(response_time),
+            Err(e) => CheckOutcome::down(url, Some(describe_error(e.as_ref()))),
+        });
+    }
+
+    // Transport errors, including failed certificate verification, mean the
+    // service is down; their cause chain becomes the failure reason.
+    let (mut result, mut timings) = match request_with_retries(url, options, http_clients).await {
+        Ok(response) => response,
+        Err(e) => return Ok(CheckOutcome::down(url, Some(describe_error(e.as_ref())))),
+    };
+
+    if options.method == Method::HEAD && matches!(result, ResponseResult::StatusError(StatusCode::METHOD_NOT_ALLOWED)) {
+        options.method = Method::GET;
+        if let Some(rate) = options.per_host_rate {
+            wait_for_host_slot(url, rate).await;
+        }
+        (result, timings) = match request_with_retries(url, options, http_clients).await {
+            Ok(response) => response,
+            Err(e) => return Ok(CheckOutcome::down(url, Some(describe_error(e.as_ref())))),
+        };
+
+        if !matches!(result, ResponseResult::StatusError(StatusCode::METHOD_NOT_ALLOWED)) {
+            db_pool.set_learned_method(service_id, Method::GET.as_str()).await?;
+        }
+    }
+
+    if let Err(e) = db_pool.add_check_timing(service_id, &redact_url(url), &timings).await {
+        eprintln!("Error adding check timings for {}: {}", name, e);
+    }
+
+    if options.expected_sha256.as_deref() == Some(LEARN_SHA256) && matches!(result, ResponseResult::Success(_)) {
+        if let Some(sha256) = &timings.body_sha256 {
+            db_pool.set_learned_sha256(service_id, sha256).await?;
+            println!("Learned the response body SHA-256 of {}: {}", name, sha256);
+        }
+    }
+
+    // Only a definite "revoked" takes the service down; an unreachable responder doesn't.
+    if options.ocsp && matches!(result, ResponseResult::Success(_)) {
+        match revocation_status(url, &options.resolve_override).await {
+            Ok(revocation) => {
+                db_pool.set_revocation_status(service_id, revocation.status).await?;
+                if revocation.status == RevocationStatus::Revoked {
+                    return Ok(CheckOutcome { reached: true, ..CheckOutcome::down(url, Some(revocation.describe())) });
+                }
+            }
+            Err(e) => eprintln!("Error checking the certificate revocation of {}: {}", name, e),
+        }
+    }
+
+    Ok(CheckOutcome::from_response(url, result))
+}
+
+/// Sends an HTTP check, repeating it up to `options.retries` times while it
+/// fails in one of the `options.retry_on` classes.
+async fn request_with_retries(
+    url: &str,
+    options: &CheckOptions,
+    http_clients: &HttpClients,
+) -> Result<(ResponseResult, PhaseTimings), Box<dyn Error>> {
+    let mut attempt = 0;
+    loop {
+        // Scoped so the boxed error isn't held across the wait below.
+        {
+            let response = get_request_response_time(url, options, http_clients).await;
+            let failure_class = match &response {
+                Ok((result, _)) => result.failure_class(),
+                Err(e) => Some(FailureClass::of_error(e.as_ref())),
+            };
+            if attempt >= options.retries || !failure_class.is_some_and(|class| options.retry_on.contains(&class)) {
+                return response;
+            }
+        }
+
+        attempt += 1;
+        sleep(CHECK_RETRY_DELAY).await;
+        if let Some(rate) = options.per_host_rate {
+            wait_for_host_slot(url, rate).await;
+        }
+    }
+}
+
+/// Combines the per-URL results of a service into a single sample. A lone URL
+/// passes through unchanged; with several, a URL whose check errors counts as down.
+fn aggregate_results(
+    urls: &[String],
+    mut results: Vec<Result<CheckOutcome, MonitoringError>>,
+    aggregation: Aggregation,
+) -> Result<CheckOutcome, MonitoringError> {
+    if results.len() == 1 {
+        return results.remove(0);
+    }
+
+    let mut response_times = Vec::new();
+    let mut failures = Vec::new();
+    // The first failing URL is the one reported in the incident metadata.
+    let mut first_failure = None;
+    for (url, result) in urls.iter().zip(results) {
+        match result {
+            Ok(outcome) if outcome.response_time != FAILED_SAMPLE => response_times.push(outcome.response_time),
+            Ok(outcome) => {
+                failures.push(format!("{}: {}", redact_url(url), outcome.failure_reason.as_deref().unwrap_or("no response")));
+                first_failure.get_or_insert(outcome);
+            }
+            Err(e) => {
+                failures.push(format!("{}: {}", redact_url(url), e));
+                first_failure.get_or_insert_with(|| CheckOutcome::down(url, None));
+            }
+        }
+    }
+
+    let down = |first_failure: Option<CheckOutcome>| CheckOutcome {
+        failure_reason: Some(failures.join("; ")),
+        ..first_failure.unwrap_or_default()
+    };
+
+    Ok(match aggregation {
+        Aggregation::Any => match response_times.iter().min() {
+            Some(&fastest) => CheckOutcome::up(fastest),
+            None => down(first_failure),
+        },
+        Aggregation::All if failures.is_empty() => CheckOutcome::up(response_times.iter().copied().max().unwrap_or(0)),
+        Aggregation::All => down(first_failure),
+    })
+}
+
+/// Probes one service right away, records the sample and opens or resolves its
+/// incidents. Shared by the monitoring cycle and the on-demand check endpoint;
+/// `sample_claimed` is set right before the sample is stored. When it was
+/// already set, the cycle deadline recorded a failed sample for this check and
+/// nothing is stored.
+#[allow(clippy::too_many_arguments)]
+pub async fn check_service(
+    service: &Service,
+    db_pool: &DbPool,
+    service_states: &ServiceStates,
+    metrics: &Metrics,
+    cache: &ServiceCache,
+    observers: &Arc<Observers>,
+    http_clients: &HttpClients,
+    config: &Config,
+    sample_claimed: &AtomicBool,
+) -> Result<RecentResult, MonitoringError> {
+    let urls = service.check_urls();
+    let aggregation = service.aggregation;
+    let options = check_options(service, config);
+    let name = &service.name;
+    let service_id = &service.id;
+    let max_samples = max_samples(service, config);
+    let rules = DetectionRules::from(service);
+    let server_url = redact_url(&service.server_url);
+    let channels = &service.channels;
+    let incident_template = &config.incident_template;
+    let degraded_incident_template = &config.degraded_incident_template;
+    let coordinate_incidents = config.coordinate_incidents;
+    let alert_cooldown = config.alert_cooldown;
+    let breaker = config.breaker_threshold.map(|threshold| (threshold, config.breaker_max_backoff));
+    let mut cached_service = service.clone();
+
+    let invert = service.invert;
+    let results = join_all(urls.iter().map(|url| {
+        let mut options = options.clone();
+        async move {
+            let checked = check_url(url, &mut options, http_clients, name, service_id, db_pool).await;
+            if invert { CheckOutcome::inverted(checked, url) } else { checked }
+        }
+    })).await;
+
+    let outcome = aggregate_results(&urls, results, aggregation)?;
+    let response_time = outcome.response_time;
+    let unit = service.response_time_unit;
+    let response_time_ms = unit.in_ms(response_time);
+
+    metrics.observe(service_id, unit.in_whole_ms(response_time));
+
+    if sample_claimed.swap(true, Ordering::AcqRel) {
+        return Err(MonitoringError(format!("Check for {} finished after the cycle deadline recorded it as failed", name)));
+    }
+    let is_online = db_pool.add_response_time(service_id, response_time, max_samples).await
+        .map_err(|e| MonitoringError(format!("Error adding response time for {}: {}", name, e)))?;
+    let result = RecentResult {
+        timestamp: Utc::now(),
+        response_time_ms: unit.in_whole_ms(response_time),
+        status_code: outcome.status_code,
+        failure_reason: outcome.failure_reason.clone(),
+        failed_url: outcome.failed_url.clone(),
+    };
+    cache.record(service_id, result.clone());
+
+    let window = rules.window();
+    let recent_failures = db_pool.count_recent_failures(service_id, window).await?;
+
+    let (transitions, baseline, breach_threshold) = {
+        let mut states = service_states.lock().await;
+        let state = states.entry(service_id.clone()).or_default();
+        let mut transitions = advance_state(state, &rules, response_time, rules.is_failing(recent_failures));
+
+        // Samples are capped at max_samples, and a full history is past any grace.
+        let samples = service.response_times.len() + 1;
+        if rules.in_grace(samples) && samples < max_samples as usize {
+            if transitions.open {
+                println!(
+                    "Not opening an incident for {} yet: first seen {}, only {} checks recorded",
+                    name, display_time(service.first_seen, config.display_timezone), samples,
+                );
+            }
+            transitions.suppress_opens();
+        }
+
+        if response_time == FAILED_SAMPLE {
+            if let Some((threshold, max_backoff)) = breaker {
+                if state.consecutive_failures >= threshold {
+                    state.backoff_cycles = (state.backoff_cycles * 2).clamp(1, max_backoff);
+                    state.skip_cycles = state.backoff_cycles;
+                }
+            }
+
+            let cooldown_elapsed = alert_cooldown.is_some_and(|cooldown| {
+                state.last_notified.is_some_and(|notified| notified.elapsed() >= cooldown)
+            });
+            transitions.remind = state.has_open_incident && cooldown_elapsed;
+            if transitions.remind {
+                state.last_notified = Some(Instant::now());
+            }
+        }
+
+        // A slow streak is announced once, then again only after the alert cooldown.
+        let breached = service.alert_on_single_breach_ms
+            .filter(|&threshold| response_time != FAILED_SAMPLE && response_time > unit.ms_to_sample(i64::from(threshold)));
+        if breached.is_none() && response_time != FAILED_SAMPLE && alert_cooldown.is_none() {
+            state.last_breach_notified = None;
+        }
+        let breach_threshold = breached.filter(|_| state.last_breach_notified.is_none_or(|notified| {
+            alert_cooldown.is_some_and(|cooldown| notified.elapsed() >= cooldown)
+        }));
+        if breach_threshold.is_some() {
+            state.last_breach_notified = Some(Instant::now());
+        }
+
+        (transitions, state.latency_ema.map(|ema| (ema, state.latency_variance)), breach_threshold)
+    };
+
+    if let Some(threshold_ms) = breach_threshold {
+        println!("{} took {}ms, over its {}ms alert threshold", name, response_time_ms, threshold_ms);
+        let breach = LatencyBreach {
+            service_id: service_id.clone(),
+            service_name: name.clone(),
+            url: server_url.clone(),
+            response_time_ms: unit.in_whole_ms(response_time),
+            threshold_ms,
+            timestamp: result.timestamp,
+        };
+        notify_latency_breach(observers, channels, &breach).await;
+    }
+
+    if service.alert_on_ip_change {
+        watch_addresses(db_pool, observers, channels, service).await;
+    }
+
+    if transitions.remind {
+        if let Ok(incidents) = db_pool.list_incidents(false).await {
+            let down_incidents = incidents.iter()
+                .filter(|incident| incident.service_id == *service_id && incident.severity == Severity::Down);
+            for incident in down_incidents {
+                notify_reminder(observers, channels, incident).await;
+            }
+        }
+    }
+
+    if transitions.open {
+        let incident_msg = match (incident_template, outcome.failure_reason) {
+            (Some(template), reason) => render_incident_template(
+                template, name, reason.as_deref().unwrap_or("no response"), recent_failures, &server_url,
+            ),
+            (None, Some(reason)) => format!("Service {} is down: {}", name, reason),
+            (None, None) => match rules.detection {
+                Detection::Consecutive => format!("Service {} is down after 5 consecutive failures", name),
+                Detection::Rate => format!(
+                    "Service {} is down: {} of the last {} checks failed", name, recent_failures, window
+                ),
+            },
+        };
+
+        let failed_url = outcome.failed_url.unwrap_or_else(|| server_url.clone());
+        let mut metadata = json!({
+            "url": failed_url,
+            "status_code": outcome.status_code,
+            "check_type": check_type(&failed_url),
+            "detection": rules.detection.as_str(),
+        });
+        if let Some(body_sha256) = outcome.body_sha256 {
+            metadata["body_sha256"] = json!(body_sha256);
+        }
+        match rules.detection {
+            Detection::Consecutive => metadata["consecutive_failures"] = json!(recent_failures),
+            Detection::Rate => {
+                metadata["failures"] = json!(recent_failures);
+                metadata["window"] = json!(window);
+            }
+        }
+
+        let incident = open_incident(
+            db_pool, coordinate_incidents, service_id, &incident_msg, Severity::Down, metadata,
+        ).await;
+        if let Ok(incident) = incident {
+            set_open_incident(service_states, service_id, Severity::Down, true).await;
+            if let Some(incident) = incident {
+                notify_open(observers, channels, &incident).await;
+            }
+        }
+    }
+
+    if transitions.close {
+        close_incidents(db_pool, coordinate_incidents, service_states, observers, channels, service_id, Severity::Down).await;
+    }
+
+    if let (true, Some(threshold)) = (transitions.open_degraded, rules.degraded_threshold_ms) {
+        let status = format!(
+            "responses took over {}ms for {} consecutive checks (last {}ms)",
+            threshold, rules.degraded_cycles, response_time_ms
+        );
+        let incident_msg = match degraded_incident_template {
+            Some(template) => render_incident_template(template, name, &status, rules.degraded_cycles, &server_url),
+            None => format!("Service {} is degraded: {}", name, status),
+        };
+
+        let metadata = json!({
+            "url": server_url,
+            "response_time_ms": response_time_ms,
+            "consecutive_slow": rules.degraded_cycles,
+            "check_type": check_type(&server_url),
+        });
+
+        let incident = open_incident(
+            db_pool, coordinate_incidents, service_id, &incident_msg, Severity::Degraded, metadata,
+        ).await;
+        if let Ok(incident) = incident {
+            set_open_incident(service_states, service_id, Severity::Degraded, true).await;
+            if let Some(incident) = incident {
+                notify_open(observers, channels, &incident).await;
+            }
+        }
+    }
+
+    if transitions.close_degraded {
+        close_incidents(db_pool, coordinate_incidents, service_states, observers, channels, service_id, Severity::Degraded).await;
+    }
+
+    if let (Some(anomaly), Some((ema, variance))) = (rules.anomaly, baseline) {
+        if response_time != FAILED_SAMPLE {
+            if let Err(e) = db_pool.set_latency_baseline(service_id, ema, variance).await {
+                eprintln!("Error saving the latency baseline for {}: {}", name, e);
+            }
+        }
+
+        if transitions.open_anomaly {
+            let (ema_ms, stddev_ms) = (ema / unit.per_ms() as f64, variance.sqrt() / unit.per_ms() as f64);
+            let incident_msg = format!(
+                "Service {} is slower than usual: {}ms, {} consecutive checks above the usual {:.0}ms ± {:.0}ms",
+                name, response_time_ms, anomaly.cycles, ema_ms, stddev_ms
+            );
+            let metadata = json!({
+                "url": server_url,
+                "response_time_ms": response_time_ms,
+                "latency_ema_ms": ema_ms,
+                "latency_stddev_ms": stddev_ms,
+                "consecutive_anomalous": anomaly.cycles,
+                "check_type": check_type(&server_url),
+            });
+
+            let incident = open_incident(
+                db_pool, coordinate_incidents, service_id, &incident_msg, Severity::Anomaly, metadata,
+            ).await;
+            if let Ok(incident) = incident {
+                set_open_incident(service_states, service_id, Severity::Anomaly, true).await;
+                if let Some(incident) = incident {
+                    notify_open(observers, channels, &incident).await;
+                }
+            }
+        }
+    }
+
+    if transitions.close_anomaly {
+        close_incidents(db_pool, coordinate_incidents, service_states, observers, channels, service_id, Severity::Anomaly).await;
+    }
+
+    let has_open_incident = service_states.lock().await.get(service_id)
+        .is_some_and(|state| state.has_open_incident || state.has_degraded_incident || state.has_anomaly_incident);
+    cached_service.response_times.push(response_time);
+    let excess = cached_service.response_times.len().saturating_sub(max_samples as usize);
+    cached_service.response_times.drain(..excess);
+    cached_service.is_online = is_online;
+    cached_service.stats = Some(db_pool.service_stats(service_id).await?);
+    cache.update(CachedService {
+        uptime_percentage: db_pool.uptime_percentage(service_id).await?,
+        service: cached_service,
+        has_open_incident,
+    });
+
+    Ok(result)
+}
+
+/// Checks every service once, records the samples and opens or resolves
+/// incidents accordingly.
+pub async fn run_once(
+    db_pool: &DbPool,
+    service_states: &ServiceStates,
+    metrics: &Metrics,
+    cache: &ServiceCache,
+    observers: &Arc<Observers>,
+    http_clients: &HttpClients,
+    config: &Config,
+) -> Result<(), MonitoringError> {
+    let task_config = Arc::new(config.clone());
+    let deadline = config.cycle_deadline.map(|cycle_deadline| tokio::time::Instant::now() + cycle_deadline);
+    let services = db_pool.list_services().await?;
+    let open_incident_ids = db_pool.list_open_incident_service_ids(Severity::Down).await?;
+    let degraded_incident_ids = db_pool.list_open_incident_service_ids(Severity::Degraded).await?;
+    let anomaly_incident_ids = db_pool.list_open_incident_service_ids(Severity::Anomaly).await?;
+    cache.set_services(services.iter().map(|service| service.id.clone()).collect());
+
+    // Incidents may have been closed outside the loop (`reset-service`), so the
+    // open flags follow the database. Services behind an open circuit breaker
+    // sit out this cycle.
+    let skipped: HashSet<String> = {
+        let mut states = service_states.lock().await;
+        services.iter()
+            .filter(|service| {
+                let state = states.entry(service.id.clone()).or_default();
+                // A service without samples was reset and starts over.
+                if service.response_times.is_empty() {
+                    *state = ServiceState::default();
+                }
+                state.has_open_incident = open_incident_ids.contains(&service.id);
+                state.has_degraded_incident = degraded_incident_ids.contains(&service.id);
+                state.has_anomaly_incident = anomaly_incident_ids.contains(&service.id);
+
+                let skip = state.skip_cycles > 0;
+                state.skip_cycles = state.skip_cycles.saturating_sub(1);
+                skip
+            })
+            .map(|service| service.id.clone())
+            .collect()
+    };
+
+    let mut monitoring_tasks = Vec::new();
+    let now = Utc::now();
+
+    for service in &services {
+        // Outside its schedule a service is neither checked nor sampled and keeps its last results.
+        if service.schedule.as_ref().is_some_and(|schedule| !schedule.is_active(now)) {
+            let has_open_incident = [&open_incident_ids, &degraded_incident_ids, &anomaly_incident_ids].iter()
+                .any(|incident_ids| incident_ids.contains(&service.id));
+            let mut cached_service = service.clone();
+            cached_service.stats = Some(db_pool.service_stats(&service.id).await?);
+            cache.update(CachedService {
+                uptime_percentage: db_pool.uptime_percentage(&service.id).await?,
+                service: cached_service,
+                has_open_incident,
+            });
+            continue;
+        }
+
+        // A skipped check is recorded as still down so that every cycle keeps one sample.
+        if skipped.contains(&service.id) {
+            metrics.observe(&service.id, FAILED_SAMPLE);
+            cache.record(&service.id, RecentResult::unchecked("skipped while the circuit breaker is open"));
+            if let Err(e) = db_pool.add_response_time(&service.id, FAILED_SAMPLE, max_samples(service, config)).await {
+                eprintln!("Error adding response time for {}: {}", service.name, e);
+            }
+            continue;
+        }
+
+        let max_samples = max_samples(service, config);
+        let task_service = service.clone();
+        let db_pool = db_pool.clone();
+        let service_states = service_states.clone();
+        let observers = observers.clone();
+        let metrics = metrics.clone();
+        let http_clients = http_clients.clone();
+        let cache = cache.clone();
+        let config = task_config.clone();
+        let sample_claimed = Arc::new(AtomicBool::new(false));
+        let task_sample_claimed = sample_claimed.clone();
+
+        let monitoring_task = tokio::spawn(async move {
+            instrument_check(&task_service, check_service(
+                &task_service, &db_pool, &service_states, &metrics, &cache, &observers, &http_clients, &config,
+                &task_sample_claimed,
+            )).await
+        });
+
+        monitoring_tasks.push((service.id.clone(), service.name.clone(), max_samples, sample_claimed, monitoring_task));
+    }
+
+    for (service_id, name, max_samples, sample_claimed, mut task) in monitoring_tasks {
+        match await_check(&mut task, deadline, &sample_claimed).await {
+            Some(Ok(Err(e))) => eprintln!("Error in monitoring task: {}", e),
+            Some(Err(e)) => eprintln!("Error in monitoring task: {}", e),
+            Some(Ok(Ok(_))) => {}
+            None => {
+                // Abandoned checks count as failed for this cycle; incidents are
+                // evaluated on the service's next completed check.
+                eprintln!("Check for {} did not finish before the cycle deadline", name);
+                metrics.observe(&service_id, FAILED_SAMPLE);
+                cache.record(&service_id, RecentResult::unchecked("check did not finish before the cycle deadline"));
+                if let Err(e) = db_pool.add_response_time(&service_id, FAILED_SAMPLE, max_samples).await {
+                    eprintln!("Error adding response time for {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Waits for a check task until the cycle deadline. At the deadline the check
+/// is aborted and `None` returned, so that the caller records it as failed,
+/// unless it already claimed its sample: then it is storing it and is awaited.
+async fn await_check<T>(
+    task: &mut JoinHandle<T>,
+    deadline: Option<tokio::time::Instant>,
+    sample_claimed: &AtomicBool,
+) -> Option<Result<T, JoinError>> {
+    let Some(deadline) = deadline else {
+        return Some(task.await);
+    };
+    if let Ok(result) = timeout_at(deadline, &mut *task).await {
+        return Some(result);
+    }
+    if sample_claimed.swap(true, Ordering::AcqRel) {
+        return Some(task.await);
+    }
+    task.abort();
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consecutive_rules() -> DetectionRules {
+        DetectionRules {
+            detection: Detection::Consecutive,
+            failure_rate: 50,
+            failure_window: 10,
+            recovery_threshold: 2,
+            degraded_threshold_ms: None,
+            degraded_cycles: 3,
+            anomaly: None,
+            min_samples: None,
+            response_time_unit: ResponseTimeUnit::Milliseconds,
+        }
+    }
+
+    #[test]
+    fn restart_with_open_incident_does_not_open_another() {
+        let rules = consecutive_rules();
+        let mut state = restored_state(&[Severity::Down], None, None, &[FAILED_SAMPLE; 5]);
+        assert!(state.has_open_incident);
+
+        let transitions = advance_state(&mut state, &rules, FAILED_SAMPLE, true);
+        assert!(!transitions.open);
+
+        advance_state(&mut state, &rules, 20, false);
+        assert!(advance_state(&mut state, &rules, 20, false).close);
+    }
+
+    #[test]
+    fn restart_without_open_incident_opens_one() {
+        let mut state = restored_state(&[], None, None, &[FAILED_SAMPLE; 5]);
+        assert!(advance_state(&mut state, &consecutive_rules(), FAILED_SAMPLE, true).open);
+    }
+
+    #[test]
+    fn restart_restores_degraded_anomaly_and_baseline() {
+        let state = restored_state(&[Severity::Degraded, Severity::Anomaly], Some(100.0), Some(25.0), &[100, FAILED_SAMPLE, 90]);
+        assert!(!state.has_open_incident);
+        assert!(state.has_degraded_incident);
+        assert!(state.has_anomaly_incident);
+        assert_eq!(state.latency_ema, Some(100.0));
+        assert_eq!(state.latency_variance, 25.0);
+        assert_eq!(state.latency_samples, 2);
+        assert!(state.last_notified.is_none());
+    }
+
+    #[test]
+    fn zero_millisecond_checks_count_as_up() {
+        let urls = ["http://127.0.0.1:8080/".to_string(), "http://127.0.0.1:8081/".to_string()];
+        let outcome = aggregate_results(&urls[..1], vec![Ok(CheckOutcome::up(0))], Aggregation::All).unwrap();
+        assert_eq!(outcome.response_time, 0);
+        assert!(outcome.failure_reason.is_none());
+
+        let results = vec![Ok(CheckOutcome::up(0)), Ok(CheckOutcome::up(3))];
+        assert_eq!(aggregate_results(&urls, results, Aggregation::All).unwrap().response_time, 3);
+        let results = vec![Ok(CheckOutcome::up(0)), Ok(CheckOutcome::down(&urls[1], None))];
+        assert_eq!(aggregate_results(&urls, results, Aggregation::Any).unwrap().response_time, 0);
+        assert_eq!(CheckOutcome::down(&urls[0], None).response_time, FAILED_SAMPLE);
+
+        for url in ["mc://127.0.0.1:25565", "ping://127.0.0.1", "sftp://127.0.0.1"] {
+            let outcome = CheckOutcome::probed(url, Ok(0));
+            assert_eq!(outcome.response_time, 0);
+            assert!(outcome.failure_reason.is_none());
+
+            let outcome = CheckOutcome::probed(url, Err("Connection refused (os error 111)".to_string()));
+            assert_eq!(outcome.response_time, FAILED_SAMPLE);
+        }
+    }
+
+    #[test]
+    fn only_failed_samples_open_incidents() {
+        let rules = consecutive_rules();
+        let mut state = restored_state(&[Severity::Down], None, None, &[]);
+        for _ in 0..rules.recovery_threshold - 1 {
+            assert!(!advance_state(&mut state, &rules, 0, false).close);
+        }
+        assert!(advance_state(&mut state, &rules, 0, false).close);
+        assert_eq!(state.consecutive_failures, 0);
+
+        assert!(crate::recompute::replay(&[0; 20], &rules).is_empty());
+        assert_eq!(crate::recompute::replay(&[FAILED_SAMPLE; 20], &rules).len(), 1);
+    }
+
+    #[test]
+    fn degraded_threshold_is_converted_to_the_sample_unit() {
+        let rules = DetectionRules {
+            degraded_threshold_ms: Some(100),
+            degraded_cycles: 1,
+            response_time_unit: ResponseTimeUnit::Microseconds,
+            ..consecutive_rules()
+        };
+        let mut state = ServiceState::default();
+        assert!(!advance_state(&mut state, &rules, 100_000, false).open_degraded);
+        assert!(advance_state(&mut state, &rules, 100_001, false).open_degraded);
+    }
+
+    #[test]
+    fn inverted_services_are_down_on_any_response() {
+        let url = "http://127.0.0.1:8080/admin";
+
+        let server_error = CheckOutcome::from_response(url, ResponseResult::StatusError(StatusCode::INTERNAL_SERVER_ERROR));
+        let outcome = CheckOutcome::inverted(Ok(server_error), url).unwrap();
+        assert_eq!(outcome.response_time, FAILED_SAMPLE);
+        assert_eq!(outcome.status_code, Some(500));
+
+        let mismatch = CheckOutcome::from_response(url, ResponseResult::ContentMismatch("body lacks \"ok\"".to_string()));
+        let outcome = CheckOutcome::inverted(Ok(mismatch), url).unwrap();
+        assert_eq!(outcome.response_time, FAILED_SAMPLE);
+        assert!(outcome.failure_reason.unwrap().contains("unexpectedly reachable"));
+    }
+
+    #[test]
+    fn inverted_services_are_up_without_an_answer() {
+        let url = "http://127.0.0.1:1/admin";
+        let refused = CheckOutcome::down(url, Some("tcp connect error: Connection refused".to_string()));
+        assert_eq!(CheckOutcome::inverted(Ok(refused), url).unwrap().response_time, 0);
+
+        let failed = Err(MonitoringError("Ping check failed".to_string()));
+        assert_eq!(CheckOutcome::inverted(failed, url).unwrap().response_time, 0);
+    }
+
+    #[tokio::test]
+    async fn deadline_waits_for_a_claimed_sample() {
+        let sample_claimed = Arc::new(AtomicBool::new(false));
+        let task_sample_claimed = sample_claimed.clone();
+        let mut task = tokio::spawn(async move {
+            task_sample_claimed.store(true, Ordering::Release);
+            sleep(Duration::from_millis(200)).await;
+            "stored"
+        });
+
+        let deadline = Some(tokio::time::Instant::now() + Duration::from_millis(50));
+        let result = await_check(&mut task, deadline, &sample_claimed).await;
+        assert_eq!(result.unwrap().unwrap(), "stored");
+    }
+
+    #[tokio::test]
+    async fn deadline_claims_the_sample_of_a_late_check() {
+        let sample_claimed = Arc::new(AtomicBool::new(false));
+        let mut task = tokio::spawn(sleep(Duration::from_secs(10)));
+
+        let deadline = Some(tokio::time::Instant::now() + Duration::from_millis(50));
+        assert!(await_check(&mut task, deadline, &sample_claimed).await.is_none());
+        assert!(sample_claimed.load(Ordering::Acquire));
+        assert!(task.await.unwrap_err().is_cancelled());
+    }
+}
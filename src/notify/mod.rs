@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::config::Config;
+use crate::database::{Incident, MonitoringError, Severity};
+
+pub mod pagerduty;
+pub mod telegram;
+pub mod webhook;
+
+
+/// Receives incident lifecycle transitions from the monitoring loop.
+#[async_trait]
+pub trait IncidentObserver: Send + Sync {
+    /// Short channel name used when reporting delivery results.
+    fn name(&self) -> &'static str;
+
+    async fn on_open(&self, incident: &Incident) -> Result<(), MonitoringError>;
+    async fn on_close(&self, incident: &Incident) -> Result<(), MonitoringError>;
+
+    /// Periodic "still down" reminder for an incident that remains open after
+    /// the alert cooldown. Channels that escalate on their own can ignore it.
+    async fn on_reminder(&self, _incident: &Incident) -> Result<(), MonitoringError> {
+        Ok(())
+    }
+
+    /// One-off notice that a single check exceeded `alert_on_single_breach_ms`.
+    /// Channels that only track incidents can ignore it.
+    async fn on_latency_breach(&self, _breach: &LatencyBreach) -> Result<(), MonitoringError> {
+        Ok(())
+    }
+
+    /// Low-severity notice that a hostname of a service with `alert_on_ip_change`
+    /// resolves to different addresses. Channels that only track incidents can ignore it.
+    async fn on_ip_change(&self, _change: &IpChange) -> Result<(), MonitoringError> {
+        Ok(())
+    }
+}
+
+/// A single successful check slower than the service's `alert_on_single_breach_ms`.
+/// Unlike degraded performance it is not tracked as an incident.
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyBreach {
+    pub service_id: String,
+    pub service_name: String,
+    pub url: String,
+    pub response_time_ms: i64,
+    pub threshold_ms: i32,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A service hostname whose A and AAAA records changed since the last cycle.
+/// It doesn't affect the service's status.
+#[derive(Debug, Clone, Serialize)]
+pub struct IpChange {
+    pub service_id: String,
+    pub service_name: String,
+    pub hostname: String,
+    pub old_addresses: Vec<String>,
+    pub new_addresses: Vec<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+pub type Observers = Vec<Box<dyn IncidentObserver>>;
+
+/// Names services can list in `channels`, matching `IncidentObserver::name`.
+pub const CHANNEL_NAMES: &[&str] = &["webhook", "pagerduty", "telegram"];
+
+/// The observers a service notifies: those named in `channels`, or all of them
+/// when the service doesn't select any.
+fn selected<'a>(observers: &'a Observers, channels: &'a [String]) -> impl Iterator<Item = &'a Box<dyn IncidentObserver>> {
+    observers.iter()
+        .filter(move |observer| channels.is_empty() || channels.iter().any(|channel| channel == observer.name()))
+}
+
+/// Builds every notification channel that is configured.
+pub fn observers_from_config(config: &Config) -> Result<Observers, MonitoringError> {
+    let mut observers: Observers = Vec::new();
+
+    if let Some(url) = &config.webhook_url {
+        observers.push(Box::new(webhook::WebhookObserver::new(url)?));
+    }
+
+    if let Some(routing_key) = &config.pagerduty_routing_key {
+        observers.push(Box::new(pagerduty::PagerDutyObserver::new(routing_key)?));
+    }
+
+    if let (Some(bot_token), Some(chat_id)) = (&config.telegram_bot_token, &config.telegram_chat_id) {
+        observers.push(Box::new(telegram::TelegramObserver::new(bot_token, chat_id, config.display_timezone)?));
+    }
+
+    Ok(observers)
+}
+
+pub async fn notify_open(observers: &Observers, channels: &[String], incident: &Incident) {
+    for observer in selected(observers, channels) {
+        if let Err(e) = observer.on_open(incident).await {
+            eprintln!("Error notifying about incident {}: {}", incident.id, e);
+        }
+    }
+}
+
+pub async fn notify_close(observers: &Observers, channels: &[String], incident: &Incident) {
+    for observer in selected(observers, channels) {
+        if let Err(e) = observer.on_close(incident).await {
+            eprintln!("Error notifying about resolved incident {}: {}", incident.id, e);
+        }
+    }
+}
+
+pub async fn notify_reminder(observers: &Observers, channels: &[String], incident: &Incident) {
+    for observer in selected(observers, channels) {
+        if let Err(e) = observer.on_reminder(incident).await {
+            eprintln!("Error sending reminder about incident {}: {}", incident.id, e);
+        }
+    }
+}
+
+pub async fn notify_latency_breach(observers: &Observers, channels: &[String], breach: &LatencyBreach) {
+    for observer in selected(observers, channels) {
+        if let Err(e) = observer.on_latency_breach(breach).await {
+            eprintln!("Error notifying about slow response of {}: {}", breach.service_name, e);
+        }
+    }
+}
+
+pub async fn notify_ip_change(observers: &Observers, channels: &[String], change: &IpChange) {
+    for observer in selected(observers, channels) {
+        if let Err(e) = observer.on_ip_change(change).await {
+            eprintln!("Error notifying about the changed addresses of {}: {}", change.service_name, e);
+        }
+    }
+}
+
+/// Sends a clearly marked test incident, opened and then resolved, through every
+/// channel and prints the outcome per channel. Returns whether all succeeded.
+pub async fn send_test_notifications(observers: &Observers) -> bool {
+    let incident = Incident {
+        id: 0,
+        service_id: "statussentinel_test".to_string(),
+        service_name: "[TEST] StatusSentinel".to_string(),
+        start_time: Utc::now(),
+        end_time: None,
+        description: "[TEST] Test notification from StatusSentinel, no service is down".to_string(),
+        severity: Severity::Down,
+        metadata: json!({ "test": true }),
+        notes: Vec::new(),
+    };
+
+    let mut all_delivered = true;
+    for observer in observers {
+        let result = match observer.on_open(&incident).await {
+            Ok(()) => observer.on_close(&Incident { end_time: Some(Utc::now()), ..incident.clone() }).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => println!("*  {}: ok", observer.name()),
+            Err(e) => {
+                all_delivered = false;
+                println!("*  {}: failed: {}", observer.name(), e);
+            }
+        }
+    }
+
+    all_delivered
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::database::{Incident, MonitoringError, Severity};
+use super::IncidentObserver;
+
+
+const EVENTS_API_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Sends PagerDuty Events API v2 `trigger` and `resolve` events using
+/// `PAGERDUTY_ROUTING_KEY`.
+pub struct PagerDutyObserver {
+    client: Client,
+    routing_key: String,
+}
+
+impl PagerDutyObserver {
+    pub fn new(routing_key: &str) -> Result<Self, MonitoringError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| MonitoringError(e.to_string()))?;
+
+        Ok(Self { client, routing_key: routing_key.to_string() })
+    }
+
+    async fn send(&self, event: Value) -> Result<(), MonitoringError> {
+        self.client.post(EVENTS_API_URL)
+            .json(&event)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| MonitoringError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// Keyed by service so that PagerDuty groups re-triggers for the same service
+/// and a resolve closes whatever alert is open for it. Degraded performance and
+/// latency anomalies are tracked as separate alerts so they resolve
+/// independently of outages.
+fn dedup_key(incident: &Incident) -> String {
+    match incident.severity {
+        Severity::Down => format!("statussentinel-{}", incident.service_id),
+        Severity::Degraded => format!("statussentinel-{}-degraded", incident.service_id),
+        Severity::Anomaly => format!("statussentinel-{}-anomaly", incident.service_id),
+    }
+}
+
+#[async_trait]
+impl IncidentObserver for PagerDutyObserver {
+    fn name(&self) -> &'static str {
+        "pagerduty"
+    }
+
+    async fn on_open(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send(json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": dedup_key(incident),
+            "payload": {
+                "summary": incident.description,
+                "source": incident.service_name,
+                "severity": match incident.severity {
+                    Severity::Down => "critical",
+                    Severity::Degraded | Severity::Anomaly => "warning",
+                },
+                "timestamp": incident.start_time,
+                "custom_details": incident,
+            },
+        })).await
+    }
+
+    async fn on_close(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send(json!({
+            "routing_key": self.routing_key,
+            "event_action": "resolve",
+            "dedup_key": dedup_key(incident),
+        })).await
+    }
+}
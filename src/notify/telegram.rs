@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use tokio::time::sleep;
+
+use crate::config::{display_duration, display_time};
+use crate::database::{Incident, MonitoringError, Severity};
+use super::{IncidentObserver, IpChange, LatencyBreach};
+
+
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Posts incident messages to a Telegram chat through the Bot API using
+/// `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID`.
+pub struct TelegramObserver {
+    client: Client,
+    bot_token: String,
+    chat_id: String,
+    timezone: Tz,
+}
+
+impl TelegramObserver {
+    pub fn new(bot_token: &str, chat_id: &str, timezone: Tz) -> Result<Self, MonitoringError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| MonitoringError(e.to_string()))?;
+
+        Ok(Self { client, bot_token: bot_token.to_string(), chat_id: chat_id.to_string(), timezone })
+    }
+
+    /// Sends a MarkdownV2 message, retrying on network errors, rate limiting
+    /// and server errors. Telegram's `retry_after` hint is honored when given.
+    async fn send(&self, text: String) -> Result<(), MonitoringError> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+        let message = json!({
+            "chat_id": self.chat_id,
+            "text": text,
+            "parse_mode": "MarkdownV2",
+        });
+
+        let mut attempt = 1;
+        loop {
+            let (error, retry_after) = match self.client.post(&url).json(&message).send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_success() {
+                        return Ok(());
+                    }
+
+                    let body = response.json::<Value>().await.unwrap_or_default();
+                    let description = body["description"].as_str().unwrap_or("no description").to_string();
+                    let error = MonitoringError(format!("Telegram API error {}: {}", status.as_u16(), description));
+
+                    let transient = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                    if !transient {
+                        return Err(error);
+                    }
+                    (error, body["parameters"]["retry_after"].as_u64())
+                }
+                // The bot token is part of the URL, so keep it out of the error.
+                Err(e) => (MonitoringError(format!("Telegram request failed: {}", e.without_url())), None),
+            };
+
+            if attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+
+            sleep(Duration::from_secs(retry_after.unwrap_or(1u64 << attempt))).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Escapes the characters MarkdownV2 reserves outside of entities.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn open_message(incident: &Incident, reminder: bool, timezone: Tz) -> String {
+    let (emoji, status) = match incident.severity {
+        Severity::Down => ("🔴", "down"),
+        Severity::Degraded => ("🟡", "degraded"),
+        Severity::Anomaly => ("🟠", "slower than usual"),
+    };
+    let status = if reminder { format!("still {}", status) } else { status.to_string() };
+
+    format!(
+        "{} *{}* is {}\n{}\nSince: {}",
+        emoji,
+        escape_markdown(&incident.service_name),
+        escape_markdown(&status),
+        escape_markdown(&incident.description),
+        escape_markdown(&display_time(incident.start_time, timezone)),
+    )
+}
+
+fn close_message(incident: &Incident, timezone: Tz) -> String {
+    let resolved = incident.end_time.map(|end_time| display_time(end_time, timezone)).unwrap_or_default();
+    format!(
+        "✅ *{}* recovered\n{}\nDowntime: {}\nResolved: {}",
+        escape_markdown(&incident.service_name),
+        escape_markdown(&incident.description),
+        escape_markdown(&display_duration(incident.duration())),
+        escape_markdown(&resolved),
+    )
+}
+
+fn breach_message(breach: &LatencyBreach, timezone: Tz) -> String {
+    format!(
+        "🐢 *{}* responded slowly\n{} took {}ms, over the {}ms threshold\nAt: {}",
+        escape_markdown(&breach.service_name),
+        escape_markdown(&breach.url),
+        breach.response_time_ms,
+        breach.threshold_ms,
+        escape_markdown(&display_time(breach.timestamp, timezone)),
+    )
+}
+
+fn ip_change_message(change: &IpChange, timezone: Tz) -> String {
+    format!(
+        "🔀 *{}* changed addresses\n{} now resolves to {}\nWas: {}\nAt: {}",
+        escape_markdown(&change.service_name),
+        escape_markdown(&change.hostname),
+        escape_markdown(&change.new_addresses.join(", ")),
+        escape_markdown(&change.old_addresses.join(", ")),
+        escape_markdown(&display_time(change.timestamp, timezone)),
+    )
+}
+
+#[async_trait]
+impl IncidentObserver for TelegramObserver {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn on_open(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send(open_message(incident, false, self.timezone)).await
+    }
+
+    async fn on_close(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send(close_message(incident, self.timezone)).await
+    }
+
+    async fn on_reminder(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send(open_message(incident, true, self.timezone)).await
+    }
+
+    async fn on_latency_breach(&self, breach: &LatencyBreach) -> Result<(), MonitoringError> {
+        self.send(breach_message(breach, self.timezone)).await
+    }
+
+    async fn on_ip_change(&self, change: &IpChange) -> Result<(), MonitoringError> {
+        self.send(ip_change_message(change, self.timezone)).await
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::database::{Incident, MonitoringError};
+use super::{IncidentObserver, IpChange, LatencyBreach};
+
+
+/// Posts a JSON payload to `WEBHOOK_URL` whenever an incident opens or closes,
+/// when a single check breaches `alert_on_single_breach_ms` and when a
+/// hostname watched with `alert_on_ip_change` resolves to new addresses.
+pub struct WebhookObserver {
+    client: Client,
+    url: String,
+}
+
+impl WebhookObserver {
+    pub fn new(url: &str) -> Result<Self, MonitoringError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| MonitoringError(e.to_string()))?;
+
+        Ok(Self { client, url: url.to_string() })
+    }
+
+    async fn send(&self, event: &str, incident: &Incident) -> Result<(), MonitoringError> {
+        self.post(&payload(event, incident)).await
+    }
+
+    async fn post(&self, payload: &Value) -> Result<(), MonitoringError> {
+        self.client.post(&self.url)
+            .json(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| MonitoringError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+fn payload(event: &str, incident: &Incident) -> Value {
+    json!({
+        "event": event,
+        "incident": incident,
+    })
+}
+
+#[async_trait]
+impl IncidentObserver for WebhookObserver {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn on_open(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send("incident.opened", incident).await
+    }
+
+    async fn on_close(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send("incident.resolved", incident).await
+    }
+
+    async fn on_reminder(&self, incident: &Incident) -> Result<(), MonitoringError> {
+        self.send("incident.reminder", incident).await
+    }
+
+    async fn on_latency_breach(&self, breach: &LatencyBreach) -> Result<(), MonitoringError> {
+        self.post(&json!({
+            "event": "latency.breach",
+            "breach": breach,
+        })).await
+    }
+
+    async fn on_ip_change(&self, change: &IpChange) -> Result<(), MonitoringError> {
+        self.post(&json!({
+            "event": "ip.changed",
+            "change": change,
+        })).await
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use openssl::{
+    asn1::Asn1GeneralizedTimeRef,
+    hash::MessageDigest,
+    ocsp::{OcspCertId, OcspCertStatus, OcspFlag, OcspRequest, OcspResponse, OcspResponseStatus, OcspRevokedStatus},
+    ssl::{SslConnector, SslMethod, SslVerifyMode},
+    stack::Stack,
+    x509::{X509, store::X509StoreBuilder},
+};
+use reqwest::{Client, Url, header::CONTENT_TYPE};
+use tokio::task::spawn_blocking;
+
+use crate::config::display_duration;
+
+
+/// Timeout of the handshake that fetches the certificate chain and of the
+/// request to the OCSP responder.
+const OCSP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a response without `nextUpdate` is reused.
+const DEFAULT_CACHE_TIME: TimeDelta = TimeDelta::hours(1);
+/// Clock skew tolerated when checking a response's `thisUpdate` and `nextUpdate`.
+const MAX_CLOCK_SKEW_SECS: u32 = 300;
+
+type OcspError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevocationStatus {
+    Good,
+    Revoked,
+    /// The responder doesn't know the certificate.
+    Unknown,
+}
+
+impl RevocationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevocationStatus::Good => "good",
+            RevocationStatus::Revoked => "revoked",
+            RevocationStatus::Unknown => "unknown",
+        }
+    }
+}
+
+/// A responder's answer about the certificate a server presents.
+#[derive(Debug, Clone)]
+pub struct Revocation {
+    pub status: RevocationStatus,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub reason: Option<&'static str>,
+    /// Until when the answer is reused.
+    next_update: DateTime<Utc>,
+}
+
+impl Revocation {
+    /// Failure reason of a check whose certificate was revoked.
+    pub fn describe(&self) -> String {
+        let mut description = "TLS certificate is revoked".to_string();
+        if let Some(revoked_at) = self.revoked_at {
+            description.push_str(&format!(" since {} ago", display_duration(Utc::now() - revoked_at)));
+        }
+        if let Some(reason) = self.reason {
+            description.push_str(&format!(" ({})", reason));
+        }
+        description
+    }
+}
+
+/// Answers by the SHA-256 fingerprint of the certificate they cover, dropped
+/// once their `nextUpdate` has passed. A rotated certificate gets its own.
+static CACHE: LazyLock<Mutex<HashMap<Vec<u8>, Revocation>>> = LazyLock::new(Default::default);
+
+/// Asks the OCSP responder named in the certificate that `url`'s server
+/// presents whether it has been revoked. Answers are reused for the same
+/// certificate until their `nextUpdate`, so responders see about one request
+/// per validity period.
+pub async fn revocation_status(url: &str, resolve_override: &BTreeMap<String, IpAddr>) -> Result<Revocation, OcspError> {
+    let parsed = Url::parse(url)?;
+    let host = parsed.host_str().ok_or("the URL has no host")?.trim_matches(['[', ']']).to_string();
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    let address = resolve_override.get(&host).copied();
+    let chain = spawn_blocking(move || peer_chain(&host, port, address)).await??;
+    let key = fingerprint(chain.first().ok_or("the server sent no certificates")?)?;
+
+    let cached = CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(&key).cloned();
+    if let Some(revocation) = cached.filter(|revocation| revocation.next_update > Utc::now()) {
+        return Ok(revocation);
+    }
+
+    let revocation = query_responder(&chain).await?;
+
+    CACHE.lock().unwrap_or_else(|e| e.into_inner()).insert(key, revocation.clone());
+    Ok(revocation)
+}
+
+fn fingerprint(cert: &X509) -> Result<Vec<u8>, OcspError> {
+    Ok(cert.digest(MessageDigest::sha256())?.to_vec())
+}
+
+/// Completes a TLS handshake and returns the server's chain, leaf first.
+fn peer_chain(host: &str, port: u16, address: Option<IpAddr>) -> Result<Vec<X509>, OcspError> {
+    let address = match address {
+        Some(ip) => SocketAddr::new(ip, port),
+        None => (host, port).to_socket_addrs()?.next().ok_or("the host has no address")?,
+    };
+    let stream = TcpStream::connect_timeout(&address, OCSP_TIMEOUT)?;
+    stream.set_read_timeout(Some(OCSP_TIMEOUT))?;
+    stream.set_write_timeout(Some(OCSP_TIMEOUT))?;
+
+    // Only the chain is needed here; the check itself validates it when verify_tls is set.
+    let mut connector = SslConnector::builder(SslMethod::tls_client())?;
+    connector.set_verify(SslVerifyMode::NONE);
+    let stream = connector.build().connect(host, stream)
+        .map_err(|e| format!("TLS handshake failed: {}", e))?;
+
+    let chain = stream.ssl().peer_cert_chain().ok_or("the server sent no certificates")?;
+    Ok(chain.iter().map(|cert| cert.to_owned()).collect())
+}
+
+async fn query_responder(chain: &[X509]) -> Result<Revocation, OcspError> {
+    let [leaf, issuer, ..] = chain else {
+        return Err("the server didn't send its issuer's certificate".into());
+    };
+
+    let responder = leaf.ocsp_responders()?.iter()
+        .map(|responder| responder.to_string())
+        .find(|responder| responder.starts_with("http://") || responder.starts_with("https://"))
+        .ok_or("the certificate names no OCSP responder")?;
+    let request = {
+        let mut request = OcspRequest::new()?;
+        request.add_id(OcspCertId::from_cert(MessageDigest::sha1(), leaf, issuer)?)?;
+        request.to_der()?
+    };
+
+    let response = Client::builder().timeout(OCSP_TIMEOUT).build()?
+        .post(&responder)
+        .header(CONTENT_TYPE, "application/ocsp-request")
+        .body(request)
+        .send().await?
+        .error_for_status()?
+        .bytes().await?;
+
+    let response = OcspResponse::from_der(&response)?;
+    if response.status() != OcspResponseStatus::SUCCESSFUL {
+        return Err(format!("the OCSP responder refused the request with status {}", response.status().as_raw()).into());
+    }
+    let basic = response.basic()?;
+
+    // The response is signed by the issuer or a responder it delegated to.
+    let mut intermediates = Stack::new()?;
+    for cert in &chain[1..] {
+        intermediates.push(cert.clone())?;
+    }
+    let mut store = X509StoreBuilder::new()?;
+    store.set_default_paths()?;
+    basic.verify(&intermediates, &store.build(), OcspFlag::empty())?;
+
+    let cert_id = OcspCertId::from_cert(MessageDigest::sha1(), leaf, issuer)?;
+    let status = basic.find_status(&cert_id).ok_or("the OCSP response doesn't cover the certificate")?;
+    status.check_validity(MAX_CLOCK_SKEW_SECS, None)?;
+
+    Ok(Revocation {
+        status: match status.status {
+            OcspCertStatus::GOOD => RevocationStatus::Good,
+            OcspCertStatus::REVOKED => RevocationStatus::Revoked,
+            _ => RevocationStatus::Unknown,
+        },
+        revoked_at: status.revocation_time.and_then(to_utc),
+        reason: (status.status == OcspCertStatus::REVOKED).then(|| revocation_reason(status.reason)).flatten(),
+        next_update: status.next_update().and_then(to_utc).unwrap_or_else(|| Utc::now() + DEFAULT_CACHE_TIME),
+    })
+}
+
+fn revocation_reason(reason: OcspRevokedStatus) -> Option<&'static str> {
+    match reason {
+        OcspRevokedStatus::KEY_COMPROMISE => Some("key compromise"),
+        OcspRevokedStatus::CA_COMPROMISE => Some("CA compromise"),
+        OcspRevokedStatus::AFFILIATION_CHANGED => Some("affiliation changed"),
+        OcspRevokedStatus::STATUS_SUPERSEDED => Some("superseded"),
+        OcspRevokedStatus::STATUS_CESSATION_OF_OPERATION => Some("cessation of operation"),
+        OcspRevokedStatus::STATUS_CERTIFICATE_HOLD => Some("certificate hold"),
+        _ => None,
+    }
+}
+
+/// Generalized times print like `Mar 14 12:00:00 2026 GMT`.
+fn to_utc(time: &Asn1GeneralizedTimeRef) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(&time.to_string(), "%b %e %H:%M:%S %Y GMT")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::{
+        asn1::Asn1Time,
+        bn::BigNum,
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        pkey::PKey,
+        x509::X509NameBuilder,
+    };
+
+    fn certificate(serial: u32) -> X509 {
+        let key = PKey::from_ec_key(EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "status.example.com").unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_serial_number(&BigNum::from_u32(serial).unwrap().to_asn1_integer().unwrap()).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(90).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn rotated_certificates_get_their_own_answer() {
+        let (current, rotated) = (certificate(1), certificate(2));
+        assert_eq!(fingerprint(&current).unwrap(), fingerprint(&current).unwrap());
+        assert_ne!(fingerprint(&current).unwrap(), fingerprint(&rotated).unwrap());
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::Write as _;
+
+use chrono::Utc;
+use chrono_tz::Tz;
+
+use crate::config::display_time;
+use crate::database::{DbPool, Detection, FAILED_SAMPLE, MonitoringError, Severity, estimate_sample_times};
+use crate::monitor::{CHECK_INTERVAL, DetectionRules, ServiceState, advance_state};
+
+
+/// Options of the `recompute-incidents` command: `recompute-incidents
+/// --service <id>`, optionally trying other detection settings than the
+/// service's, e.g. `--detection rate --failure-rate 30`.
+pub struct RecomputeArgs {
+    pub service_id: String,
+    pub detection: Option<Detection>,
+    pub failure_rate: Option<i32>,
+    pub failure_window: Option<i32>,
+    pub recovery_threshold: Option<i32>,
+    pub degraded_threshold_ms: Option<i32>,
+    pub degraded_cycles: Option<i32>,
+}
+
+impl RecomputeArgs {
+    pub fn parse(args: &[String]) -> Result<Self, MonitoringError> {
+        let mut service_id = None;
+        let mut recompute_args = RecomputeArgs {
+            service_id: String::new(),
+            detection: None,
+            failure_rate: None,
+            failure_window: None,
+            recovery_threshold: None,
+            degraded_threshold_ms: None,
+            degraded_cycles: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args.next()
+                .ok_or_else(|| MonitoringError(format!("{} needs a value", arg)))?;
+            let number = || value.parse::<i32>()
+                .ok()
+                .filter(|number| *number >= 1)
+                .ok_or_else(|| MonitoringError(format!("{} must be a positive number, got {}", arg, value)));
+
+            match arg.as_str() {
+                "--service" => service_id = Some(value.clone()),
+                "--detection" => recompute_args.detection = Some(Detection::from_name(value)
+                    .ok_or_else(|| MonitoringError(format!("Unknown detection {}, use consecutive or rate", value)))?),
+                "--failure-rate" => recompute_args.failure_rate = Some(number()?.min(100)),
+                "--failure-window" => recompute_args.failure_window = Some(number()?),
+                "--recovery-threshold" => recompute_args.recovery_threshold = Some(number()?),
+                "--degraded-threshold-ms" => recompute_args.degraded_threshold_ms = Some(number()?),
+                "--degraded-cycles" => recompute_args.degraded_cycles = Some(number()?),
+                other => return Err(MonitoringError(format!("Unknown recompute-incidents option {}", other))),
+            }
+        }
+
+        recompute_args.service_id = service_id
+            .ok_or_else(|| MonitoringError("recompute-incidents needs --service <id>".to_string()))?;
+        Ok(recompute_args)
+    }
+
+    fn apply(&self, rules: &mut DetectionRules) {
+        rules.detection = self.detection.unwrap_or(rules.detection);
+        rules.failure_rate = self.failure_rate.unwrap_or(rules.failure_rate);
+        rules.failure_window = self.failure_window.unwrap_or(rules.failure_window);
+        rules.recovery_threshold = self.recovery_threshold.unwrap_or(rules.recovery_threshold);
+        rules.degraded_threshold_ms = self.degraded_threshold_ms.or(rules.degraded_threshold_ms);
+        rules.degraded_cycles = self.degraded_cycles.unwrap_or(rules.degraded_cycles);
+    }
+}
+
+/// Incident the detection logic would have opened, by sample index (0 is the oldest).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayedIncident {
+    pub severity: Severity,
+    pub start: usize,
+    /// The sample that resolved it, `None` when still open after the last one.
+    pub end: Option<usize>,
+}
+
+/// Runs the samples through the same detection as the monitoring loop, as if
+/// the service had started without incidents. Nothing is written.
+pub fn replay(response_times: &[i64], rules: &DetectionRules) -> Vec<ReplayedIncident> {
+    let mut state = ServiceState::default();
+    let mut incidents = Vec::new();
+    let mut down_start = None;
+    let mut degraded_start = None;
+    let mut anomaly_start = None;
+
+    for (index, &response_time) in response_times.iter().enumerate() {
+        let window_start = (index + 1).saturating_sub(rules.window().max(1) as usize);
+        let recent_failures = response_times[window_start..=index].iter().filter(|&&time| time == FAILED_SAMPLE).count() as i32;
+        let mut transitions = advance_state(&mut state, rules, response_time, rules.is_failing(recent_failures));
+        if rules.in_grace(index + 1) {
+            transitions.suppress_opens();
+        }
+
+        if transitions.open {
+            state.has_open_incident = true;
+            down_start = Some(index);
+        }
+        if transitions.close {
+            state.has_open_incident = false;
+            if let Some(start) = down_start.take() {
+                incidents.push(ReplayedIncident { severity: Severity::Down, start, end: Some(index) });
+            }
+        }
+        if transitions.open_degraded && rules.degraded_threshold_ms.is_some() {
+            state.has_degraded_incident = true;
+            degraded_start = Some(index);
+        }
+        if transitions.close_degraded {
+            state.has_degraded_incident = false;
+            if let Some(start) = degraded_start.take() {
+                incidents.push(ReplayedIncident { severity: Severity::Degraded, start, end: Some(index) });
+            }
+        }
+        if transitions.open_anomaly {
+            state.has_anomaly_incident = true;
+            anomaly_start = Some(index);
+        }
+        if transitions.close_anomaly {
+            state.has_anomaly_incident = false;
+            if let Some(start) = anomaly_start.take() {
+                incidents.push(ReplayedIncident { severity: Severity::Anomaly, start, end: Some(index) });
+            }
+        }
+    }
+
+    let open_incidents = [
+        (Severity::Down, down_start),
+        (Severity::Degraded, degraded_start),
+        (Severity::Anomaly, anomaly_start),
+    ];
+    for (severity, start) in open_incidents {
+        if let Some(start) = start {
+            incidents.push(ReplayedIncident { severity, start, end: None });
+        }
+    }
+    incidents.sort_by_key(|incident| incident.start);
+    incidents
+}
+
+/// Replays the service's stored samples and renders the incidents as a table,
+/// timed by when the samples were taken. Samples recorded before their times
+/// were stored get one estimated from the check interval, marked with `~`.
+pub async fn recompute_incidents(db_pool: &DbPool, args: &RecomputeArgs, timezone: Tz) -> Result<String, MonitoringError> {
+    let service = db_pool.get_service(&args.service_id).await?
+        .ok_or_else(|| MonitoringError(format!("No service with id {}", args.service_id)))?;
+
+    let mut rules = DetectionRules::from(&service);
+    args.apply(&mut rules);
+
+    let history = db_pool.sample_history(&service.id).await?;
+    let response_times: Vec<i64> = history.iter().map(|(response_time, _)| *response_time).collect();
+    let times: Vec<_> = history.iter().map(|(_, time)| *time).collect();
+    let times = estimate_sample_times(&times, CHECK_INTERVAL, Utc::now());
+    let samples = response_times.len();
+    let sample_time = |index: usize| -> String {
+        let (time, estimated) = times[index];
+        format!("{}{}", if estimated { "~" } else { "" }, display_time(time, timezone))
+    };
+
+    let incidents = replay(&response_times, &rules);
+
+    let mut table = String::new();
+    writeln!(table, "{:<10} {:<22} {:<22} {:>9}", "Severity", "Start", "End", "Checks").unwrap();
+    for incident in &incidents {
+        let end = incident.end
+            .map_or_else(|| "ongoing".to_string(), sample_time);
+        let checks = incident.end.unwrap_or(samples) - incident.start;
+        writeln!(
+            table,
+            "{:<10} {:<22} {:<22} {:>9}",
+            incident.severity.as_str(), sample_time(incident.start), end, checks,
+        ).unwrap();
+    }
+
+    write!(
+        table,
+        "{} incident(s) from {} samples of {} with {} detection",
+        incidents.len(), samples, service.name, rules.detection.as_str()
+    ).unwrap();
+    Ok(table)
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+
+/// Recurring window in which a service is checked, such as weekdays from 9 to
+/// 5. Outside of it the service is left alone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Days the window opens on, every day when empty.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Opening time as `HH:MM`.
+    pub start: String,
+    /// Closing time as `HH:MM`, earlier than `start` for windows past midnight.
+    pub end: String,
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+}
+
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if parse_time(&self.start)? == parse_time(&self.end)? {
+            return Err("schedule start and end must differ".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let (Ok(start), Ok(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return true;
+        };
+        let local = now.with_timezone(&self.timezone);
+        let time = local.time();
+
+        // Past midnight, the window still belongs to the day it opened on.
+        let (in_window, day) = if start < end {
+            (start <= time && time < end, local.weekday())
+        } else if time >= start {
+            (true, local.weekday())
+        } else {
+            (time < end, local.weekday().pred())
+        };
+
+        in_window && (self.days.is_empty() || self.days.contains(&day))
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|_| format!("invalid schedule time {:?}, expected HH:MM", value))
+}
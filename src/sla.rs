@@ -0,0 +1,102 @@
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::database::{DbPool, MonitoringError};
+use crate::monitor::CHECK_INTERVAL;
+
+
+pub const DEFAULT_TARGET_PERCENTAGE: f64 = 99.9;
+pub const DEFAULT_WINDOW_DAYS: i32 = 30;
+
+/// Error budget of one service for an SLA target over a window of days.
+#[derive(Debug, Clone, Serialize)]
+pub struct SlaReport {
+    pub service_id: String,
+    pub name: String,
+    pub target_percentage: f64,
+    /// Uptime over the samples recorded in the window, `None` without samples.
+    pub actual_percentage: Option<f64>,
+    pub allowed_downtime_minutes: f64,
+    pub downtime_minutes: f64,
+    /// Negative once the budget is exhausted.
+    pub remaining_budget_minutes: f64,
+}
+
+/// Builds the report for every service. Samples are taken once per check
+/// interval, so the window is the matching number of most recent samples and
+/// each failed sample counts as one interval of downtime.
+pub async fn sla_reports(db_pool: &DbPool, target_percentage: f64, window_days: i32) -> Result<Vec<SlaReport>, MonitoringError> {
+    if !(0.0..=100.0).contains(&target_percentage) || window_days < 1 {
+        return Err(MonitoringError("SLA target must be between 0 and 100 and the window at least one day".to_string()));
+    }
+
+    let interval_minutes = CHECK_INTERVAL.as_secs_f64() / 60.0;
+    let window_minutes = f64::from(window_days) * 24.0 * 60.0;
+    let window_samples = (window_minutes / interval_minutes) as i32;
+    let allowed_downtime_minutes = window_minutes * (100.0 - target_percentage) / 100.0;
+
+    let reports = db_pool.count_samples(window_samples).await?
+        .into_iter()
+        .map(|counts| {
+            let downtime_minutes = counts.failed as f64 * interval_minutes;
+            SlaReport {
+                actual_percentage: (counts.total > 0)
+                    .then(|| 100.0 * (counts.total - counts.failed) as f64 / counts.total as f64),
+                service_id: counts.service_id,
+                name: counts.name,
+                target_percentage,
+                allowed_downtime_minutes,
+                downtime_minutes,
+                remaining_budget_minutes: allowed_downtime_minutes - downtime_minutes,
+            }
+        })
+        .collect();
+
+    Ok(reports)
+}
+
+/// Options of the `sla-report` command: `sla-report [--target 99.9] [--days 30]`.
+pub fn parse_args(args: &[String]) -> Result<(f64, i32), MonitoringError> {
+    let mut target_percentage = DEFAULT_TARGET_PERCENTAGE;
+    let mut window_days = DEFAULT_WINDOW_DAYS;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next()
+            .ok_or_else(|| MonitoringError(format!("{} needs a value", arg)))?;
+
+        match arg.as_str() {
+            "--target" => target_percentage = value.parse()
+                .map_err(|_| MonitoringError(format!("--target must be a percentage, got {}", value)))?,
+            "--days" => window_days = value.parse()
+                .map_err(|_| MonitoringError(format!("--days must be a number of days, got {}", value)))?,
+            other => return Err(MonitoringError(format!("Unknown sla-report option {}", other))),
+        }
+    }
+
+    Ok((target_percentage, window_days))
+}
+
+pub fn render_table(reports: &[SlaReport], window_days: i32) -> String {
+    let mut table = String::new();
+    writeln!(
+        table,
+        "{:<30} {:>9} {:>9} {:>13} {:>13}",
+        "Service", "Target", "Actual", "Budget (min)", "Left (min)"
+    ).unwrap();
+
+    for report in reports {
+        let actual = report.actual_percentage
+            .map_or_else(|| "-".to_string(), |actual| format!("{:.3}%", actual));
+        writeln!(
+            table,
+            "{:<30} {:>8.3}% {:>9} {:>13.1} {:>13.1}",
+            report.name, report.target_percentage, actual,
+            report.allowed_downtime_minutes, report.remaining_budget_minutes,
+        ).unwrap();
+    }
+
+    write!(table, "Window: last {} days", window_days).unwrap();
+    table
+}
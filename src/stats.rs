@@ -0,0 +1,85 @@
+use std::fmt::Write as _;
+
+use crate::database::{DbPool, FAILED_SAMPLE, MonitoringError, Severity};
+
+
+/// Services listed under "slowest" in the summary.
+const SLOWEST_COUNT: usize = 5;
+
+/// Snapshot of every monitored service, printed by the `stats` command.
+#[derive(Debug, Clone)]
+pub struct FleetStats {
+    pub services: usize,
+    pub up: usize,
+    pub open_incidents: usize,
+    pub open_down: usize,
+    pub open_degraded: usize,
+    pub open_anomaly: usize,
+    /// Mean of the services' uptime over their recorded samples, `None` before any sample.
+    pub average_uptime: Option<f64>,
+    /// Names and mean response times of the slowest services over their
+    /// recorded successful samples, slowest first.
+    pub slowest: Vec<(String, f64)>,
+}
+
+pub async fn fleet_stats(db_pool: &DbPool) -> Result<FleetStats, MonitoringError> {
+    let services = db_pool.list_services().await?;
+    let incidents = db_pool.list_incidents(false).await?;
+    let open_with = |severity: Severity| incidents.iter().filter(|incident| incident.severity == severity).count();
+
+    let mut uptimes = Vec::new();
+    for service in &services {
+        uptimes.extend(db_pool.uptime_percentage(&service.id).await?);
+    }
+
+    let mut slowest: Vec<(String, f64)> = services.iter()
+        .filter_map(|service| {
+            let successful: Vec<i64> = service.response_times.iter()
+                .copied()
+                .filter(|&response_time| response_time != FAILED_SAMPLE)
+                .collect();
+            let samples_per_ms = service.response_time_unit.per_ms() as f64;
+            (!successful.is_empty())
+                .then(|| (service.name.clone(), successful.iter().sum::<i64>() as f64 / successful.len() as f64 / samples_per_ms))
+        })
+        .collect();
+    slowest.sort_by(|a, b| b.1.total_cmp(&a.1));
+    slowest.truncate(SLOWEST_COUNT);
+
+    Ok(FleetStats {
+        services: services.len(),
+        up: services.iter().filter(|service| service.is_online).count(),
+        open_incidents: incidents.len(),
+        open_down: open_with(Severity::Down),
+        open_degraded: open_with(Severity::Degraded),
+        open_anomaly: open_with(Severity::Anomaly),
+        average_uptime: (!uptimes.is_empty()).then(|| uptimes.iter().sum::<f64>() / uptimes.len() as f64),
+        slowest,
+    })
+}
+
+pub fn render_summary(stats: &FleetStats) -> String {
+    let mut summary = String::new();
+    writeln!(
+        summary,
+        "{:<16} {} ({} up, {} down)",
+        "Services:", stats.services, stats.up, stats.services - stats.up
+    ).unwrap();
+    writeln!(
+        summary,
+        "{:<16} {} ({} down, {} degraded, {} anomaly)",
+        "Open incidents:", stats.open_incidents, stats.open_down, stats.open_degraded, stats.open_anomaly
+    ).unwrap();
+    let average_uptime = stats.average_uptime
+        .map_or_else(|| "-".to_string(), |uptime| format!("{:.3}%", uptime));
+    writeln!(summary, "{:<16} {}", "Average uptime:", average_uptime).unwrap();
+
+    write!(summary, "Slowest services (mean of recent successful checks):").unwrap();
+    if stats.slowest.is_empty() {
+        write!(summary, "\n  -").unwrap();
+    }
+    for (name, mean_ms) in &stats.slowest {
+        write!(summary, "\n  {:<30} {:>8.0}ms", name, mean_ms).unwrap();
+    }
+    summary
+}
@@ -0,0 +1,178 @@
+use std::fmt::Write;
+
+use chrono_tz::Tz;
+
+use crate::config::display_time;
+use crate::database::{FAILED_SAMPLE, Incident, SYSTEM_NOTE_AUTHOR, Service};
+
+
+pub struct ServiceStatus {
+    pub service: Service,
+    pub uptime_percentage: Option<f64>,
+}
+
+fn escape_html(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const STYLE: &str = "
+    body { font-family: system-ui, sans-serif; max-width: 760px; margin: 40px auto; padding: 0 16px; color: #1f2328; }
+    h1 { font-size: 1.6em; }
+    table { width: 100%; border-collapse: collapse; }
+    th, td { text-align: left; padding: 10px 8px; border-bottom: 1px solid #d0d7de; }
+    .badge { display: inline-block; padding: 2px 10px; border-radius: 12px; color: #fff; font-size: 0.85em; }
+    .up { background: #1a7f37; }
+    .down { background: #cf222e; }
+    .incident { border-left: 4px solid #cf222e; padding: 8px 12px; margin: 8px 0; background: #fff5f5; }
+    .incident.degraded { border-left-color: #bf8700; background: #fff8c5; }
+    .incident.anomaly { border-left-color: #bc4c00; background: #fff1e5; }
+    .notes { margin: 6px 0 0; padding-left: 18px; color: #424a53; font-size: 0.9em; }
+    footer { margin-top: 32px; color: #656d76; font-size: 0.85em; }
+";
+
+/// Renders a self-contained status page that refreshes itself every minute.
+pub fn render(statuses: &[ServiceStatus], open_incidents: &[Incident], timezone: Tz) -> String {
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    html.push_str("<meta charset=\"utf-8\">\n<meta http-equiv=\"refresh\" content=\"60\">\n");
+    html.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    writeln!(html, "<title>Status</title>\n<style>{}</style>\n</head>\n<body>", STYLE).unwrap();
+
+    let all_up = statuses.iter().all(|status| status.service.is_online);
+    writeln!(
+        html,
+        "<h1>{}</h1>",
+        if all_up { "All systems operational" } else { "Some systems are experiencing issues" }
+    ).unwrap();
+
+    if !open_incidents.is_empty() {
+        html.push_str("<h2>Open incidents</h2>\n");
+        for incident in open_incidents {
+            write!(
+                html,
+                "<div class=\"incident {}\"><strong>{}</strong> &middot; since {}<br>{}",
+                incident.severity.as_str(),
+                escape_html(&incident.service_name),
+                display_time(incident.start_time, timezone),
+                escape_html(&incident.description),
+            ).unwrap();
+
+            // The opening note repeats the description, so only updates by people are listed.
+            let notes: Vec<_> = incident.notes.iter().filter(|note| note.author != SYSTEM_NOTE_AUTHOR).collect();
+            if !notes.is_empty() {
+                html.push_str("<ul class=\"notes\">");
+                for note in notes {
+                    write!(
+                        html,
+                        "<li>{} &middot; {}: {}</li>",
+                        display_time(note.timestamp, timezone),
+                        escape_html(&note.author),
+                        escape_html(&note.note),
+                    ).unwrap();
+                }
+                html.push_str("</ul>");
+            }
+            html.push_str("</div>\n");
+        }
+    }
+
+    html.push_str("<h2>Services</h2>\n<table>\n");
+    html.push_str("<tr><th>Service</th><th>Status</th><th>Response time</th><th>Uptime</th></tr>\n");
+    for status in statuses {
+        let service = &status.service;
+        let (class, label) = if service.is_online { ("up", "Up") } else { ("down", "Down") };
+        let response_time = match service.response_times.last() {
+            Some(&time) if time != FAILED_SAMPLE => format!("{} ms", service.response_time_unit.in_ms(time)),
+            _ => "&ndash;".to_string(),
+        };
+        let uptime = match status.uptime_percentage {
+            Some(uptime) => format!("{:.2}%", uptime),
+            None => "&ndash;".to_string(),
+        };
+
+        writeln!(
+            html,
+            "<tr><td>{}</td><td><span class=\"badge {}\">{}</span></td><td>{}</td><td>{}</td></tr>",
+            escape_html(&service.name), class, label, response_time, uptime,
+        ).unwrap();
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<footer>Powered by <a href=\"https://github.com/tn3w/statussentinel\">StatusSentinel</a></footer>\n");
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+/// Approximate width of text in 11px Verdana, which the badge is set in.
+fn badge_text_width(text: &str) -> usize {
+    text.chars().count() * 7
+}
+
+/// Renders a flat two-part badge like the ones from shields.io: the label on
+/// grey and the message on the given color.
+pub fn render_badge(label: &str, message: &str, color: &str) -> String {
+    let label_width = badge_text_width(label) + 10;
+    let message_width = badge_text_width(message) + 10;
+    let width = label_width + message_width;
+    let (label, message) = (escape_html(label), escape_html(message));
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"20\" role=\"img\" aria-label=\"{}: {}\">",
+        width, label, message,
+    ).unwrap();
+    writeln!(svg, "<title>{}: {}</title>", label, message).unwrap();
+    svg.push_str("<linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/>");
+    svg.push_str("<stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\n");
+    writeln!(svg, "<clipPath id=\"r\"><rect width=\"{}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>", width).unwrap();
+    writeln!(
+        svg,
+        "<g clip-path=\"url(#r)\"><rect width=\"{}\" height=\"20\" fill=\"#555\"/><rect x=\"{}\" width=\"{}\" height=\"20\" fill=\"{}\"/>\
+         <rect width=\"{}\" height=\"20\" fill=\"url(#s)\"/></g>",
+        label_width, label_width, message_width, color, width,
+    ).unwrap();
+    writeln!(
+        svg,
+        "<g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\
+         <text x=\"{}\" y=\"14\">{}</text><text x=\"{}\" y=\"14\">{}</text></g>",
+        label_width / 2, label, label_width + message_width / 2, message,
+    ).unwrap();
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+    use crate::database::Severity;
+
+    #[test]
+    fn incident_descriptions_are_escaped() {
+        let incident = Incident {
+            id: 1,
+            service_id: "api".to_string(),
+            service_name: "API".to_string(),
+            start_time: Utc::now(),
+            end_time: None,
+            description: "<script>alert(1)</script> latency < 200ms".to_string(),
+            severity: Severity::Down,
+            metadata: json!({}),
+            notes: Vec::new(),
+        };
+
+        let page = render(&[], &[incident], Tz::UTC);
+        assert!(page.contains("&lt;script&gt;alert(1)&lt;/script&gt; latency &lt; 200ms"));
+        assert!(!page.contains("<script>"));
+    }
+}
@@ -0,0 +1,153 @@
+use std::future::Future;
+
+#[cfg(feature = "otel")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "otel")]
+use opentelemetry::{
+    KeyValue, global,
+    metrics::{Gauge, Histogram},
+    trace::{Span, Status, Tracer},
+};
+#[cfg(feature = "otel")]
+use opentelemetry_otlp::{MetricExporter, SpanExporter};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::{Resource, metrics::SdkMeterProvider, trace::SdkTracerProvider};
+
+use crate::cache::RecentResult;
+use crate::config::Config;
+use crate::database::{MonitoringError, Service};
+#[cfg(feature = "otel")]
+use crate::database::FAILED_SAMPLE;
+#[cfg(feature = "otel")]
+use crate::request::redact_url;
+
+
+/// The OpenTelemetry providers installed by `init`. Dropping it flushes the
+/// spans and metrics still buffered, so a `--once` pass doesn't lose them.
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    providers: Option<(SdkTracerProvider, SdkMeterProvider)>,
+}
+
+/// Installs OTLP exporters for traces and metrics when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set. Otherwise the global providers stay
+/// no-ops and checks are not instrumented.
+#[cfg(feature = "otel")]
+pub fn init(config: &Config) -> Result<Telemetry, MonitoringError> {
+    if config.otlp_endpoint.is_none() {
+        return Ok(Telemetry::default());
+    }
+
+    // The exporters read the endpoint and OTEL_EXPORTER_OTLP_HEADERS themselves
+    // and append the per-signal paths (/v1/traces, /v1/metrics).
+    let resource = Resource::builder().with_service_name("statussentinel").build();
+    let span_exporter = SpanExporter::builder().with_http().build()
+        .map_err(|e| MonitoringError(format!("Failed to set up the OTLP span exporter: {}", e)))?;
+    let metric_exporter = MetricExporter::builder().with_http().build()
+        .map_err(|e| MonitoringError(format!("Failed to set up the OTLP metric exporter: {}", e)))?;
+
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_batch_exporter(span_exporter)
+        .with_resource(resource.clone())
+        .build();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_periodic_exporter(metric_exporter)
+        .with_resource(resource)
+        .build();
+
+    global::set_tracer_provider(tracer_provider.clone());
+    global::set_meter_provider(meter_provider.clone());
+
+    Ok(Telemetry { providers: Some((tracer_provider, meter_provider)) })
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn init(config: &Config) -> Result<Telemetry, MonitoringError> {
+    if config.otlp_endpoint.is_some() {
+        eprintln!("OTEL_EXPORTER_OTLP_ENDPOINT is set, but this build doesn't include the otel feature");
+    }
+    Ok(Telemetry::default())
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some((tracer_provider, meter_provider)) = &self.providers {
+            if let Err(e) = tracer_provider.shutdown() {
+                eprintln!("Error flushing OpenTelemetry spans: {}", e);
+            }
+            if let Err(e) = meter_provider.shutdown() {
+                eprintln!("Error flushing OpenTelemetry metrics: {}", e);
+            }
+        }
+    }
+}
+
+/// `statussentinel.up` (1 or 0) and `statussentinel.response_time` (ms, successful checks only).
+#[cfg(feature = "otel")]
+fn instruments() -> &'static (Gauge<u64>, Histogram<f64>) {
+    static INSTRUMENTS: OnceLock<(Gauge<u64>, Histogram<f64>)> = OnceLock::new();
+    INSTRUMENTS.get_or_init(|| {
+        let meter = global::meter("statussentinel");
+        (
+            meter.u64_gauge("statussentinel.up")
+                .with_description("Whether the service's last check succeeded")
+                .build(),
+            meter.f64_histogram("statussentinel.response_time")
+                .with_description("Response time of successful checks")
+                .with_unit("ms")
+                .build(),
+        )
+    })
+}
+
+/// Runs a service's check inside a `check_service` span with the service's
+/// attributes and records the outcome as metrics.
+#[cfg(feature = "otel")]
+pub async fn instrument_check(
+    service: &Service,
+    check: impl Future<Output = Result<RecentResult, MonitoringError>>,
+) -> Result<RecentResult, MonitoringError> {
+    let attributes = [
+        KeyValue::new("statussentinel.service.id", service.id.clone()),
+        KeyValue::new("statussentinel.service.name", service.name.clone()),
+    ];
+    let mut span = global::tracer("statussentinel").start("check_service");
+    span.set_attributes(attributes.clone());
+    span.set_attribute(KeyValue::new("url.full", redact_url(&service.server_url)));
+
+    let result = check.await;
+
+    match &result {
+        Ok(result) => {
+            let up = result.response_time_ms != FAILED_SAMPLE;
+            span.set_attribute(KeyValue::new("statussentinel.response_time_ms", result.response_time_ms));
+            if let Some(status_code) = result.status_code {
+                span.set_attribute(KeyValue::new("http.response.status_code", i64::from(status_code)));
+            }
+            if !up {
+                span.set_status(Status::error(result.failure_reason.clone().unwrap_or_else(|| "down".to_string())));
+            }
+
+            let (up_gauge, response_time_histogram) = instruments();
+            up_gauge.record(u64::from(up), &attributes);
+            if up {
+                response_time_histogram.record(result.response_time_ms as f64, &attributes);
+            }
+        }
+        Err(e) => span.set_status(Status::error(e.to_string())),
+    }
+    span.end();
+
+    result
+}
+
+#[cfg(not(feature = "otel"))]
+pub async fn instrument_check(
+    _service: &Service,
+    check: impl Future<Output = Result<RecentResult, MonitoringError>>,
+) -> Result<RecentResult, MonitoringError> {
+    check.await
+}
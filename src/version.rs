@@ -0,0 +1,35 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+
+/// Identifies the running build, from the metadata embedded by build.rs.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_timestamp: Option<DateTime<Utc>>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("STATUSSENTINEL_GIT_COMMIT"),
+            build_timestamp: env!("STATUSSENTINEL_BUILD_TIMESTAMP").parse()
+                .ok()
+                .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+        }
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (commit {}", self.version, self.git_commit)?;
+        if let Some(build_timestamp) = self.build_timestamp {
+            write!(f, ", built {}", build_timestamp.format("%Y-%m-%d %H:%M:%S UTC"))?;
+        }
+        write!(f, ")")
+    }
+}